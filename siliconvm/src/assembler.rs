@@ -0,0 +1,896 @@
+// Assembler for the textual form of Silicon bytecode (`.sasm` files).
+//
+// A source file is a sequence of lines, each holding at most one of:
+//
+//   .const NAME VALUE    adds VALUE to the constant table under NAME
+//   .func NAME (TYPE, ...) -> TYPE [captures CAPTURE, ...]
+//                        adds a function to the constant table under NAME; its
+//                        body follows as a nested listing closed by `.end`.
+//                        `closure NAME` captures register slot N for each
+//                        `rN` and the enclosing closure's captured variable N
+//                        for each `cN`, in order
+//   .class NAME CANONICAL_NAME [extends CLASS] [implements INTERFACE, ...]
+//                        registers a class type and adds the class to the
+//                        constant table under NAME; `.field [const] NAME TYPE`
+//                        and `.method NAME (TYPE, ...) -> TYPE` blocks follow,
+//                        closed by `.end`
+//   .interface NAME CANONICAL_NAME [extends INTERFACE, ...]
+//                        the same for an interface, which has no fields
+//   .handler NAME LABEL [TYPE]
+//                        adds an exception handler to the handler table under
+//                        NAME, catching exceptions of TYPE, or of any type, at
+//                        LABEL once installed by `try NAME`
+//   .switch NAME DEFAULT adds a table to the switch table under NAME for
+//                        `switch NAME`, which jumps to DEFAULT unless one of
+//                        the `.case KEY LABEL` lines that follow, closed by
+//                        `.end`, has the value it pops as its KEY. Keys are
+//                        integers, written with or without a suffix, chars
+//                        or strings
+//   NAME:                defines a jump label at the next instruction
+//   [OFFSET] MNEMONIC    an instruction, optionally prefixed with its offset
+//
+// Everything after a `;` outside of a string or char literal is a comment.
+// Constant slots, jump labels, handlers and switches are numbered in the order they are
+// defined, and all may be referred to before their definition. Type operands are
+// canonical type names, or `#INDEX` for a raw `TypeRegistry` index. The output
+// of the disassembler is accepted as-is, so listings can be edited and
+// reassembled.
+//
+// Parameterised types are written without spaces, as in
+// `silicon.lang.Array<silicon.lang.Int32>`, and a generic class declares its
+// type parameters after its name, as in `.class Some silicon.lang.Some<T>`.
+// A generic parent is given the type arguments it is reified with, which may
+// use the class's own type parameters, as in
+// `.class Some silicon.lang.Some<T> extends Optional<T>`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use crate::instance::{Instance, Function, Type, Class, Field, Capture, TypeArg};
+use crate::opcode::{Chunk, OpCode, Handler, SwitchTable, SwitchKey};
+use crate::runtime::{TypeRegistry, split_type_list};
+use crate::string_pool::StringPool;
+use crate::verifier;
+use crate::error::ErrorKind;
+use crate::decimal::{Decimal, RoundingMode, MAX_SCALE};
+
+#[derive(Debug)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String
+}
+
+impl AssemblyError {
+    fn new(line: usize, message: String) -> AssemblyError {
+        AssemblyError {
+            line,
+            message
+        }
+    }
+}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+pub fn assemble(source: &str, string_pool: &mut StringPool, type_registry: &mut TypeRegistry) -> Result<Chunk, AssemblyError> {
+    let lines: Vec<(usize, &str)> = source.lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, strip_comment(line).trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    let chunk = assemble_block(&lines, string_pool, type_registry)?;
+    // Labels are resolved as they are assembled, so this only fails on a bug,
+    // but the chunk is held to the same checks as one read from a file.
+    match verifier::verify(&chunk) {
+        Ok(()) => Ok(chunk),
+        Err(error) => Err(AssemblyError::new(source.lines().count(), error.to_string()))
+    }
+}
+
+fn assemble_block(lines: &[(usize, &str)], string_pool: &mut StringPool, type_registry: &mut TypeRegistry) -> Result<Chunk, AssemblyError> {
+    let mut assembler = Assembler {
+        string_pool,
+        type_registry,
+        constants: HashMap::new(),
+        labels: HashMap::new(),
+        handlers: HashMap::new(),
+        switches: HashMap::new(),
+        chunk: Chunk::new()
+    };
+
+    // The first pass gives every constant and label its number, so that the
+    // second can resolve references that come before a definition.
+    let mut instructions: Vec<(usize, &str)> = vec![];
+    let mut handlers: Vec<(usize, &str)> = vec![];
+    // Where each `.switch` block starts and ends.
+    let mut switches: Vec<(usize, usize)> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let (number, line) = lines[i];
+        if let Some(rest) = line.strip_prefix(".const") {
+            assembler.define_const(number, rest.trim())?;
+        }
+        else if let Some(rest) = line.strip_prefix(".func") {
+            let end = find_end(lines, i)?;
+            assembler.define_func(number, rest.trim(), &lines[i + 1..end])?;
+            i = end;
+        }
+        else if let Some(rest) = line.strip_prefix(".class") {
+            let end = find_end(lines, i)?;
+            assembler.define_class(number, false, rest.trim(), &lines[i + 1..end])?;
+            i = end;
+        }
+        else if let Some(rest) = line.strip_prefix(".interface") {
+            let end = find_end(lines, i)?;
+            assembler.define_class(number, true, rest.trim(), &lines[i + 1..end])?;
+            i = end;
+        }
+        else if line == ".end" {
+            return Err(AssemblyError::new(number, "`.end` without a matching block".to_string()))
+        }
+        else if line.starts_with(".field") || line.starts_with(".method") {
+            return Err(AssemblyError::new(number, format!("`{}` outside of a `.class`", line)))
+        }
+        else if line.starts_with(".case") {
+            return Err(AssemblyError::new(number, format!("`{}` outside of a `.switch`", line)))
+        }
+        else if let Some(rest) = line.strip_prefix(".handler") {
+            handlers.push((number, rest.trim()));
+        }
+        else if line.starts_with(".switch") {
+            let end = find_end(lines, i)?;
+            switches.push((i, end));
+            i = end;
+        }
+        else if let Some(name) = line.strip_suffix(':') {
+            assembler.define_label(number, name.trim(), instructions.len())?;
+        }
+        else {
+            instructions.push((number, line));
+        }
+        i += 1;
+    }
+
+    // Handlers and switches name labels, so they are only defined once every label is.
+    for (number, rest) in handlers {
+        assembler.define_handler(number, rest)?;
+    }
+    for (start, end) in switches {
+        let (number, line) = lines[start];
+        assembler.define_switch(number, line[".switch".len()..].trim(), &lines[start + 1..end])?;
+    }
+
+    for (number, line) in instructions {
+        let op = assembler.parse_instruction(number, line)?;
+        assembler.chunk.write(op);
+    }
+
+    Ok(assembler.chunk)
+}
+
+// Finds the `.end` closing the block opened at `start`, skipping nested blocks.
+fn find_end(lines: &[(usize, &str)], start: usize) -> Result<usize, AssemblyError> {
+    let mut depth = 0;
+    for (i, (_, line)) in lines.iter().enumerate().skip(start) {
+        if [".func", ".class", ".interface", ".method", ".switch"].iter().any(|directive| line.starts_with(directive)) {
+            depth += 1
+        }
+        else if *line == ".end" {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(i)
+            }
+        }
+    }
+    let directive = lines[start].1.split_whitespace().next().unwrap_or_default();
+    Err(AssemblyError::new(lines[start].0, format!("`{}` is missing its `.end`", directive)))
+}
+
+struct Assembler<'a> {
+    string_pool: &'a mut StringPool,
+    type_registry: &'a mut TypeRegistry,
+    constants: HashMap<String, u16>,
+    labels: HashMap<String, u16>,
+    handlers: HashMap<String, u16>,
+    switches: HashMap<String, u16>,
+    chunk: Chunk
+}
+
+impl <'a> Assembler<'a> {
+    fn define_const(&mut self, line: usize, rest: &str) -> Result<(), AssemblyError> {
+        let (name, value) = match rest.find(char::is_whitespace) {
+            Some(split) => (&rest[..split], rest[split..].trim()),
+            None => return Err(AssemblyError::new(line, "expected `.const NAME VALUE`".to_string()))
+        };
+        check_identifier(line, name)?;
+        if self.constants.contains_key(name) {
+            return Err(AssemblyError::new(line, format!("constant `{}` is already defined", name)))
+        }
+
+        let constant = parse_value(value, self.string_pool).map_err(|message| AssemblyError::new(line, message))?;
+        let slot = self.constants.len() as u16;
+        self.constants.insert(name.to_string(), slot);
+        self.chunk.add_const(slot, constant);
+        Ok(())
+    }
+
+    fn define_func(&mut self, line: usize, header: &str, body: &[(usize, &str)]) -> Result<(), AssemblyError> {
+        let (name, function) = self.parse_function(line, ".func", header, body)?;
+        self.check_const_name(line, name)?;
+        let slot = self.constants.len() as u16;
+        self.constants.insert(name.to_string(), slot);
+        self.chunk.add_const(slot, Instance::Func(Rc::new(function)));
+        Ok(())
+    }
+
+    // Registers the class's type up front, so that its fields, its methods and
+    // the rest of the listing can refer to it by name. The header is
+    // `NAME CANONICAL_NAME [extends CLASS] [implements INTERFACE, ...]` for
+    // classes and `NAME CANONICAL_NAME [extends INTERFACE, ...]` for
+    // interfaces, where the parents are the names of earlier constants.
+    fn define_class(&mut self, line: usize, is_interface: bool, header: &str, body: &[(usize, &str)]) -> Result<(), AssemblyError> {
+        let malformed = || match is_interface {
+            true => AssemblyError::new(line, "expected `.interface NAME CANONICAL_NAME [extends INTERFACE, ...]`".to_string()),
+            false => AssemblyError::new(line, "expected `.class NAME CANONICAL_NAME [extends CLASS] [implements INTERFACE, ...]`".to_string())
+        };
+        let mut tokens = header.split_whitespace();
+        let (name, canonical_name) = match (tokens.next(), tokens.next()) {
+            (Some(name), Some(canonical_name)) => (name, canonical_name),
+            _ => return Err(malformed())
+        };
+        self.check_const_name(line, name)?;
+
+        // Parents may be given type arguments with commas in them, so each
+        // list is split once it has been put back together.
+        let mut extends = String::new();
+        let mut implements = String::new();
+        let mut in_implements: Option<bool> = None;
+        for token in tokens {
+            match (token, in_implements) {
+                ("extends", None) => in_implements = Some(false),
+                ("implements", None) | ("implements", Some(false)) if !is_interface => in_implements = Some(true),
+                (_, Some(false)) => extends.push_str(token),
+                (_, Some(true)) => implements.push_str(token),
+                (_, None) => return Err(malformed())
+            }
+        }
+        let mut extends = split_type_list(&extends);
+        let mut implements = split_type_list(&implements);
+        if is_interface {
+            implements = extends;
+            extends = vec![];
+        }
+        if extends.len() > 1 {
+            return Err(AssemblyError::new(line, "a class can only extend one class".to_string()))
+        }
+
+        // The names of the type parameters are only used by the parents' type
+        // arguments, which refer to them by position.
+        let (canonical_name, type_params) = match (canonical_name.find('<'), canonical_name.strip_suffix('>')) {
+            (Some(open), Some(rest)) => (&canonical_name[..open], split_type_list(&rest[open + 1..])),
+            (None, None) => (canonical_name, vec![]),
+            _ => return Err(malformed())
+        };
+        if type_params.len() > u8::MAX as usize {
+            return Err(AssemblyError::new(line, "too many type parameters".to_string()))
+        }
+
+        let superclass = match extends.first() {
+            Some(parent) => Some(self.resolve_parent(line, parent, false, &type_params)?),
+            None => None
+        };
+        let mut interfaces = vec![];
+        for parent in implements {
+            interfaces.push(self.resolve_parent(line, parent, true, &type_params)?);
+        }
+
+        let supertype = superclass.as_ref().map(|(superclass, args)| (Rc::clone(&superclass._type), args.clone()));
+        let interface_types = interfaces.iter().map(|(interface, args)| (Rc::clone(&interface._type), args.clone())).collect();
+        let _type = self.type_registry.define_type(self.string_pool.pool_str(canonical_name), type_params.len(), supertype, interface_types)
+            .map_err(|kind| AssemblyError::new(line, kind.to_string()))?;
+        let superclass = superclass.map(|(superclass, _)| superclass);
+        let interfaces = interfaces.into_iter().map(|(interface, _)| interface).collect();
+
+        let mut fields: Vec<Field> = vec![];
+        let mut methods = HashMap::new();
+        let mut i = 0;
+        while i < body.len() {
+            let (number, member) = body[i];
+            if let Some(rest) = member.strip_prefix(".field") {
+                if is_interface {
+                    return Err(AssemblyError::new(number, "interfaces cannot declare fields".to_string()))
+                }
+                let tokens: Vec<&str> = rest.split_whitespace().collect();
+                let (is_const, field_name, type_name) = match tokens[..] {
+                    ["const", field_name, type_name] => (true, field_name, type_name),
+                    [field_name, type_name] => (false, field_name, type_name),
+                    _ => return Err(AssemblyError::new(number, "expected `.field [const] NAME TYPE`".to_string()))
+                };
+                check_identifier(number, field_name)?;
+                if fields.iter().any(|field| *field.name == field_name) {
+                    return Err(AssemblyError::new(number, format!("field `{}` is already defined", field_name)))
+                }
+                let field_type = self.resolve_type_ref(number, type_name)?;
+                fields.push(Field::new(self.string_pool.pool_str(field_name), field_type, is_const));
+            }
+            else if let Some(rest) = member.strip_prefix(".method") {
+                let end = find_end(body, i)?;
+                let (method_name, function) = self.parse_function(number, ".method", rest.trim(), &body[i + 1..end])?;
+                let method_name = self.string_pool.pool_str(method_name);
+                if methods.contains_key(&method_name) {
+                    return Err(AssemblyError::new(number, format!("method `{}` is already defined", method_name)))
+                }
+                methods.insert(method_name, Rc::new(function));
+                i = end;
+            }
+            else {
+                return Err(AssemblyError::new(number, format!("expected `.field` or `.method` but found `{}`", member)))
+            }
+            i += 1;
+        }
+
+        let slot = self.constants.len() as u16;
+        self.constants.insert(name.to_string(), slot);
+        let class = match is_interface {
+            true => Class::new_interface(_type, interfaces, methods),
+            false => Class::new(_type, superclass, interfaces, fields, methods)
+        };
+        self.chunk.add_const(slot, Instance::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    // Resolves a parent of a class, written as the name of an earlier `.class`
+    // or `.interface` constant followed by the type arguments passed to it if
+    // it is generic, as in `Map<K,silicon.lang.String>`. Each argument is one
+    // of `type_params` or a type name that may use them.
+    fn resolve_parent(&mut self, line: usize, parent: &str, is_interface: bool, type_params: &[&str]) -> Result<(Rc<Class>, Vec<TypeArg>), AssemblyError> {
+        let (name, args) = match (parent.find('<'), parent.strip_suffix('>')) {
+            (Some(open), Some(rest)) => (&parent[..open], split_type_list(&rest[open + 1..])),
+            (None, None) => (parent, vec![]),
+            _ => return Err(AssemblyError::new(line, format!("malformed parent `{}`", parent)))
+        };
+        let class = self.resolve_class(line, name, is_interface)?;
+        let mut type_args = vec![];
+        for arg in args {
+            type_args.push(self.resolve_type_arg(line, arg, type_params)?);
+        }
+        Ok((class, type_args))
+    }
+
+    fn resolve_type_arg(&mut self, line: usize, arg: &str, type_params: &[&str]) -> Result<TypeArg, AssemblyError> {
+        if let Some(index) = type_params.iter().position(|param| *param == arg) {
+            return Ok(TypeArg::Param(index))
+        }
+        if let (Some(open), Some(rest)) = (arg.find('<'), arg.strip_suffix('>')) {
+            let mut args = vec![];
+            for inner in split_type_list(&rest[open + 1..]) {
+                args.push(self.resolve_type_arg(line, inner, type_params)?);
+            }
+            if args.iter().any(TypeArg::uses_params) {
+                return Ok(TypeArg::Applied(self.resolve_type_ref(line, &arg[..open])?, args))
+            }
+        }
+        Ok(TypeArg::Concrete(self.resolve_type_ref(line, arg)?))
+    }
+
+    // Resolves the name of an earlier `.class` or `.interface` constant.
+    fn resolve_class(&self, line: usize, name: &str, is_interface: bool) -> Result<Rc<Class>, AssemblyError> {
+        let slot = self.resolve_const(line, name)?;
+        match self.chunk.const_table.get(&slot) {
+            Some(Instance::Class(class)) if class.is_interface == is_interface => Ok(Rc::clone(class)),
+            _ => {
+                let kind = if is_interface { "an interface" } else { "a class" };
+                Err(AssemblyError::new(line, format!("`{}` is not {}", name, kind)))
+            }
+        }
+    }
+
+    fn check_const_name(&self, line: usize, name: &str) -> Result<(), AssemblyError> {
+        check_identifier(line, name)?;
+        if self.constants.contains_key(name) {
+            return Err(AssemblyError::new(line, format!("constant `{}` is already defined", name)))
+        }
+        Ok(())
+    }
+
+    // Parses `NAME (TYPE, ...) -> TYPE` and assembles `body` into the function.
+    fn parse_function<'h>(&mut self, line: usize, directive: &str, header: &'h str, body: &[(usize, &str)]) -> Result<(&'h str, Function), AssemblyError> {
+        let malformed = || AssemblyError::new(line, format!("expected `{} NAME (TYPE, ...) -> TYPE`", directive));
+        let open = header.find('(').ok_or_else(malformed)?;
+        let close = header.rfind(')').ok_or_else(malformed)?;
+        if close < open {
+            return Err(malformed())
+        }
+
+        let name = header[..open].trim();
+        check_identifier(line, name)?;
+
+        let mut param_types = vec![];
+        for param in split_type_list(&header[open + 1..close]) {
+            param_types.push(self.resolve_type_ref(line, param)?);
+        }
+        let (rest, captures) = match header[close + 1..].split_once("captures") {
+            Some((rest, captures)) => (rest, split_type_list(captures)),
+            None => (&header[close + 1..], vec![])
+        };
+        let return_type = match rest.trim() {
+            "" => self.resolve_type_ref(line, "silicon.lang.Void")?,
+            rest => match rest.strip_prefix("->") {
+                Some(return_type) => self.resolve_type_ref(line, return_type.trim())?,
+                None => return Err(malformed())
+            }
+        };
+
+        let captures = captures.into_iter().map(|capture| parse_capture(line, capture)).collect::<Result<Vec<Capture>, AssemblyError>>()?;
+
+        let chunk = assemble_block(body, self.string_pool, self.type_registry)?;
+        let mut function = Function::new(Rc::new(chunk), param_types, return_type);
+        function.captures = captures;
+        Ok((name, function))
+    }
+
+    fn define_handler(&mut self, line: usize, rest: &str) -> Result<(), AssemblyError> {
+        let (name, label, catch_type) = match rest.split_whitespace().collect::<Vec<&str>>()[..] {
+            [name, label] => (name, label, None),
+            [name, label, catch_type] => (name, label, Some(catch_type)),
+            _ => return Err(AssemblyError::new(line, "expected `.handler NAME LABEL [TYPE]`".to_string()))
+        };
+        check_identifier(line, name)?;
+        if self.handlers.contains_key(name) {
+            return Err(AssemblyError::new(line, format!("handler `{}` is already defined", name)))
+        }
+        let label = self.resolve_label(line, label)?;
+        let catch_type = match catch_type {
+            Some(catch_type) => Some(self.resolve_type(line, catch_type)?),
+            None => None
+        };
+
+        let index = self.handlers.len() as u16;
+        self.handlers.insert(name.to_string(), index);
+        self.chunk.handler_table.insert(index, Handler { label, catch_type });
+        Ok(())
+    }
+
+    fn resolve_handler(&self, line: usize, name: &str) -> Result<u16, AssemblyError> {
+        match self.handlers.get(name) {
+            Some(index) => Ok(*index),
+            None => Err(AssemblyError::new(line, format!("handler `{}` is not defined", name)))
+        }
+    }
+
+    fn define_switch(&mut self, line: usize, rest: &str, body: &[(usize, &str)]) -> Result<(), AssemblyError> {
+        let (name, default) = match rest.split_whitespace().collect::<Vec<&str>>()[..] {
+            [name, default] => (name, default),
+            _ => return Err(AssemblyError::new(line, "expected `.switch NAME DEFAULT`".to_string()))
+        };
+        check_identifier(line, name)?;
+        if self.switches.contains_key(name) {
+            return Err(AssemblyError::new(line, format!("switch `{}` is already defined", name)))
+        }
+        let default = self.resolve_label(line, default)?;
+
+        let mut cases: Vec<(SwitchKey, u16)> = vec![];
+        for (number, case) in body {
+            // The label comes last, since a string key may contain spaces.
+            let (key, label) = match case.strip_prefix(".case").map(str::trim).and_then(|case| case.rsplit_once(char::is_whitespace)) {
+                Some((key, label)) => (key.trim(), label),
+                None => return Err(AssemblyError::new(*number, "expected `.case KEY LABEL`".to_string()))
+            };
+            let parsed = self.parse_switch_key(*number, key)?;
+            if cases.iter().any(|(other, _)| *other == parsed) {
+                return Err(AssemblyError::new(*number, format!("switch `{}` already has a case for {}", name, key)))
+            }
+            cases.push((parsed, self.resolve_label(*number, label)?));
+        }
+
+        let index = self.switches.len() as u16;
+        self.switches.insert(name.to_string(), index);
+        self.chunk.switch_table.insert(index, SwitchTable::new(default, cases));
+        Ok(())
+    }
+
+    // Integers are read as i128s when they have no suffix, so that keys
+    // outside the range of a `silicon.lang.Int32` can be written as the
+    // disassembler prints them.
+    fn parse_switch_key(&mut self, line: usize, text: &str) -> Result<SwitchKey, AssemblyError> {
+        if let Ok(value) = text.parse::<i128>() {
+            return Ok(SwitchKey::Int(value))
+        }
+        let value = parse_value(text, self.string_pool).map_err(|message| AssemblyError::new(line, message))?;
+        match SwitchKey::of(&value) {
+            Some(key) => Ok(key),
+            None => Err(AssemblyError::new(line, format!("`{}` cannot be a switch key, expected an integer, char or string", text)))
+        }
+    }
+
+    fn resolve_switch(&self, line: usize, name: &str) -> Result<u16, AssemblyError> {
+        match self.switches.get(name) {
+            Some(index) => Ok(*index),
+            None => Err(AssemblyError::new(line, format!("switch `{}` is not defined", name)))
+        }
+    }
+
+    fn define_label(&mut self, line: usize, name: &str, offset: usize) -> Result<(), AssemblyError> {
+        check_identifier(line, name)?;
+        if self.labels.contains_key(name) {
+            return Err(AssemblyError::new(line, format!("label `{}` is already defined", name)))
+        }
+
+        let label = self.labels.len() as u16;
+        self.labels.insert(name.to_string(), label);
+        self.chunk.jump_table.insert(label, offset);
+        Ok(())
+    }
+
+    fn parse_instruction(&mut self, line: usize, text: &str) -> Result<OpCode, AssemblyError> {
+        let mut tokens: Vec<&str> = text.split_whitespace().collect();
+
+        if tokens[0].chars().all(|c| c.is_ascii_digit()) {
+            let expected = self.chunk.op_codes.len();
+            if tokens[0].parse::<usize>().ok() != Some(expected) {
+                return Err(AssemblyError::new(line, format!("offset {} does not match the instruction's position {:04}", tokens[0], expected)))
+            }
+            tokens.remove(0);
+        }
+
+        let (mnemonic, operands) = match tokens.split_first() {
+            Some((mnemonic, operands)) => (*mnemonic, operands),
+            None => return Err(AssemblyError::new(line, "expected an instruction after the offset".to_string()))
+        };
+
+        let expected_operands = match mnemonic {
+            "get" | "get_const" | "get_native" | "declare" | "declare_const" | "set" | "is" | "convert" | "jump" | "jump_if_false" | "jump_if_true" | "try" | "switch"
+            | "closure" | "get_captured" | "set_captured"
+            | "enter_scope" | "get_field" | "set_field" | "invoke" | "new_generic" => 1,
+            "init_array" | "divide_rounded" | "format" => 2,
+            _ => 0
+        };
+        if operands.len() != expected_operands {
+            return Err(AssemblyError::new(line, format!("`{}` takes {} operand(s) but {} were given", mnemonic, expected_operands, operands.len())))
+        }
+
+        let op = match mnemonic {
+            "get_true" => OpCode::GetTrue,
+            "get_false" => OpCode::GetFalse,
+            "get_const" => OpCode::Get(true, self.resolve_const(line, operands[0])?),
+            "get" => OpCode::Get(false, parse_u16(line, operands[0])?),
+            "declare" => OpCode::Declare(false, self.resolve_type(line, operands[0])?),
+            "declare_const" => OpCode::Declare(true, self.resolve_type(line, operands[0])?),
+            "set" => OpCode::Set(parse_u16(line, operands[0])?),
+            "add" => OpCode::Add,
+            "subtract" => OpCode::Subtract,
+            "multiply" => OpCode::Multiply,
+            "divide" => OpCode::Divide,
+            "divide_rounded" => OpCode::DivideRounded(parse_scale(line, operands[0])?, parse_rounding_mode(line, operands[1])?),
+            "power" => OpCode::Power,
+            "modulo" => OpCode::Modulo,
+            "bit_and" => OpCode::BitAnd,
+            "bit_or" => OpCode::BitOr,
+            "bit_xor" => OpCode::BitXor,
+            "bit_not" => OpCode::BitNot,
+            "shift_left" => OpCode::ShiftLeft,
+            "shift_right" => OpCode::ShiftRight,
+            "int_negate" => OpCode::IntNegate,
+            "logic_negate" => OpCode::LogicNegate,
+            "less" => OpCode::Less,
+            "greater" => OpCode::Greater,
+            "less_or_eq" => OpCode::LessOrEq,
+            "greater_or_eq" => OpCode::GreaterOrEq,
+            "eq" => OpCode::Eq,
+            "not_eq" => OpCode::NotEq,
+            "is" => OpCode::Is(self.resolve_type(line, operands[0])?),
+            "convert" => OpCode::Convert(self.resolve_type(line, operands[0])?),
+            "concat" => OpCode::Concat,
+            "format" => OpCode::Format(self.resolve_const(line, operands[0])?, parse_u8(line, operands[1])?),
+            "jump" => OpCode::Jump(false, self.resolve_label(line, operands[0])?),
+            "jump_if_false" => OpCode::Jump(true, self.resolve_label(line, operands[0])?),
+            "jump_if_true" => OpCode::JumpIfTrue(self.resolve_label(line, operands[0])?),
+            "call" => OpCode::Call,
+            "return" => OpCode::Return(false),
+            "return_value" => OpCode::Return(true),
+            "init_array" => OpCode::InitArray(parse_u16(line, operands[0])?, self.resolve_type(line, operands[1])?),
+            "index_get" => OpCode::IndexGet,
+            "index_set" => OpCode::IndexSet,
+            "enter_scope" => OpCode::EnterScope(parse_u16(line, operands[0])?),
+            "exit_scope" => OpCode::ExitScope,
+            "print" => OpCode::Print,
+            "get_native" => OpCode::GetNative(self.resolve_const(line, operands[0])?),
+            "new" => OpCode::New,
+            "new_generic" => OpCode::NewGeneric(self.resolve_type(line, operands[0])?),
+            "get_field" => OpCode::GetField(parse_u16(line, operands[0])?),
+            "set_field" => OpCode::SetField(parse_u16(line, operands[0])?),
+            "invoke" => OpCode::Invoke(self.resolve_const(line, operands[0])?),
+            "closure" => OpCode::Closure(self.resolve_const(line, operands[0])?),
+            "get_captured" => OpCode::GetCaptured(parse_u16(line, operands[0])?),
+            "set_captured" => OpCode::SetCaptured(parse_u16(line, operands[0])?),
+            "throw" => OpCode::Throw,
+            "try" => OpCode::Try(self.resolve_handler(line, operands[0])?),
+            "switch" => OpCode::Switch(self.resolve_switch(line, operands[0])?),
+            "end_try" => OpCode::EndTry,
+            "yield" => OpCode::Yield,
+            "resume" => OpCode::Resume,
+            _ => return Err(AssemblyError::new(line, format!("unknown instruction `{}`", mnemonic)))
+        };
+        Ok(op)
+    }
+
+    fn resolve_const(&self, line: usize, name: &str) -> Result<u16, AssemblyError> {
+        match self.constants.get(name) {
+            Some(slot) => Ok(*slot),
+            None => Err(AssemblyError::new(line, format!("constant `{}` is not defined", name)))
+        }
+    }
+
+    fn resolve_label(&self, line: usize, name: &str) -> Result<u16, AssemblyError> {
+        match self.labels.get(name) {
+            Some(label) => Ok(*label),
+            None => Err(AssemblyError::new(line, format!("label `{}` is not defined", name)))
+        }
+    }
+
+    fn resolve_type_ref(&mut self, line: usize, name: &str) -> Result<Rc<Type>, AssemblyError> {
+        let index = self.resolve_type(line, name)?;
+        self.type_registry.get(index).map_err(|kind| AssemblyError::new(line, kind.to_string()))
+    }
+
+    fn resolve_type(&mut self, line: usize, name: &str) -> Result<u16, AssemblyError> {
+        if let Some(index) = name.strip_prefix('#') {
+            let index = parse_u16(line, index)?;
+            return match self.type_registry.try_get(index) {
+                Some(_) => Ok(index),
+                None => Err(AssemblyError::new(line, format!("type index {} does not exist", index)))
+            }
+        }
+
+        match self.type_registry.resolve_name(name) {
+            Ok(index) => Ok(index),
+            Err(ErrorKind::UnknownType(_)) => Err(AssemblyError::new(line, format!("type `{}` does not exist", name))),
+            Err(kind) => Err(AssemblyError::new(line, kind.to_string()))
+        }
+    }
+}
+
+fn check_identifier(line: usize, name: &str) -> Result<(), AssemblyError> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(first) => (first.is_alphabetic() || first == '_') && chars.all(|c| c.is_alphanumeric() || c == '_'),
+        None => false
+    };
+    if valid {
+        return Ok(())
+    }
+    Err(AssemblyError::new(line, format!("`{}` is not a valid name", name)))
+}
+
+fn parse_u8(line: usize, text: &str) -> Result<u8, AssemblyError> {
+    match text.parse::<u8>() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(AssemblyError::new(line, format!("`{}` is not a valid 8-bit operand", text)))
+    }
+}
+
+fn parse_u16(line: usize, text: &str) -> Result<u16, AssemblyError> {
+    match text.parse::<u16>() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(AssemblyError::new(line, format!("`{}` is not a valid 16-bit operand", text)))
+    }
+}
+
+// Parses `rN` for register slot N or `cN` for captured variable N.
+fn parse_capture(line: usize, text: &str) -> Result<Capture, AssemblyError> {
+    let invalid = || AssemblyError::new(line, format!("`{}` is not a valid capture, expected `rN` or `cN`", text));
+    let index = text.get(1..).and_then(|index| index.parse::<u16>().ok()).ok_or_else(invalid)?;
+    match text.chars().next() {
+        Some('r') => Ok(Capture::Local(index)),
+        Some('c') => Ok(Capture::Captured(index)),
+        _ => Err(invalid())
+    }
+}
+
+fn parse_scale(line: usize, text: &str) -> Result<u8, AssemblyError> {
+    match text.parse::<u8>() {
+        Ok(scale) if scale <= MAX_SCALE => Ok(scale),
+        _ => Err(AssemblyError::new(line, format!("`{}` is not a valid scale of at most {} digits", text, MAX_SCALE)))
+    }
+}
+
+fn parse_rounding_mode(line: usize, text: &str) -> Result<RoundingMode, AssemblyError> {
+    match RoundingMode::from_name(text) {
+        Some(mode) => Ok(mode),
+        None => Err(AssemblyError::new(line, format!("`{}` is not a rounding mode", text)))
+    }
+}
+
+// Removes a trailing `;` comment, ignoring semicolons inside literals.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false
+                }
+                else if c == '\\' {
+                    escaped = true
+                }
+                else if c == q {
+                    quote = None
+                }
+            },
+            None => {
+                if c == ';' {
+                    return &line[..i]
+                }
+                if c == '"' || c == '\'' {
+                    quote = Some(c)
+                }
+            }
+        }
+    }
+    line
+}
+
+// Parses a constant written the way `disassembler::format_constant` prints it.
+fn parse_value(text: &str, string_pool: &mut StringPool) -> Result<Instance, String> {
+    match text {
+        "true" => return Ok(Instance::Bool(true)),
+        "false" => return Ok(Instance::Bool(false)),
+        "void" => return Ok(Instance::Void),
+        _ => {}
+    }
+
+    if text.starts_with('"') {
+        let string = parse_quoted(text, '"')?;
+        return Ok(Instance::Str(string_pool.pool_string(string)))
+    }
+
+    if text.starts_with('\'') {
+        let string = parse_quoted(text, '\'')?;
+        let mut chars = string.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Instance::Char(c)),
+            _ => Err(format!("{} is not a single character", text))
+        }
+    }
+
+    parse_number(text)
+}
+
+fn parse_number(text: &str) -> Result<Instance, String> {
+    // Complex numbers are written as `RE+IMi` or `IMi`, which no suffix ends like.
+    if text.ends_with('i') && !text.ends_with("bi") {
+        return match text.parse() {
+            Ok(complex) => Ok(Instance::Complex(complex)),
+            Err(_) => Err(format!("`{}` is not a valid complex constant", text))
+        }
+    }
+
+    // Longer suffixes come first so that `ub` is not mistaken for `b`, nor `ubi` for `bi`.
+    const SUFFIXES: [&str; 24] = ["ud128", "d128", "ud16", "ud32", "ud64", "d16", "d32", "d64",
+        "i128", "u128", "ui32", "i16", "u16", "i32", "u32", "i64", "u64", "f32", "f64", "ubi", "bi", "ub", "b", ""];
+
+    let invalid = || format!("`{}` is not a valid constant", text);
+    let suffix = SUFFIXES.iter().find(|suffix| text.ends_with(*suffix)).unwrap();
+    let digits = &text[..text.len() - suffix.len()];
+    let is_float = digits.contains('.');
+
+    let instance = match *suffix {
+        "b" => Instance::Byte(digits.parse().map_err(|_| invalid())?),
+        "ub" => Instance::UByte(digits.parse().map_err(|_| invalid())?),
+        "i16" => Instance::Int16(digits.parse().map_err(|_| invalid())?),
+        "u16" => Instance::UInt16(digits.parse().map_err(|_| invalid())?),
+        "i32" => Instance::Int32(digits.parse().map_err(|_| invalid())?),
+        "ui32" | "u32" => Instance::UInt32(digits.parse().map_err(|_| invalid())?),
+        "i64" => Instance::Int64(digits.parse().map_err(|_| invalid())?),
+        "u64" => Instance::UInt64(digits.parse().map_err(|_| invalid())?),
+        "i128" => Instance::Int128(digits.parse().map_err(|_| invalid())?),
+        "u128" => Instance::UInt128(digits.parse().map_err(|_| invalid())?),
+        "f32" => Instance::Float32(digits.parse().map_err(|_| invalid())?),
+        "f64" => Instance::Float64(digits.parse().map_err(|_| invalid())?),
+        "bi" => Instance::BigInt(digits.parse().map_err(|_| invalid())?),
+        "ubi" => Instance::UBigInt(digits.parse().map_err(|_| invalid())?),
+        "d16" => Instance::Decimal16(Decimal::parse(digits).ok_or_else(invalid)?),
+        "ud16" => Instance::UDecimal16(Decimal::parse(digits).ok_or_else(invalid)?),
+        "d32" => Instance::Decimal32(Decimal::parse(digits).ok_or_else(invalid)?),
+        "ud32" => Instance::UDecimal32(Decimal::parse(digits).ok_or_else(invalid)?),
+        "d64" => Instance::Decimal64(Decimal::parse(digits).ok_or_else(invalid)?),
+        "ud64" => Instance::UDecimal64(Decimal::parse(digits).ok_or_else(invalid)?),
+        "d128" => Instance::Decimal128(Decimal::parse(digits).ok_or_else(invalid)?),
+        "ud128" => Instance::UDecimal128(Decimal::parse(digits).ok_or_else(invalid)?),
+        _ if is_float => Instance::Float64(digits.parse().map_err(|_| invalid())?),
+        _ => Instance::Int32(digits.parse().map_err(|_| invalid())?)
+    };
+    Ok(instance)
+}
+
+// Reads a literal delimited by `quote`, handling the escapes Rust's `Debug`
+// output produces for strings and chars.
+fn parse_quoted(text: &str, quote: char) -> Result<String, String> {
+    let unterminated = || format!("unterminated literal {}", text);
+    if text.len() < 2 || !text.ends_with(quote) {
+        return Err(unterminated())
+    }
+
+    let mut result = String::new();
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c == quote {
+            return Err(format!("unescaped {} in literal {}", quote, text))
+        }
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('u') => {
+                let rest = chars.as_str();
+                let close = match (rest.starts_with('{'), rest.find('}')) {
+                    (true, Some(close)) => close,
+                    _ => return Err(format!("malformed unicode escape in {}", text))
+                };
+                let code = u32::from_str_radix(&rest[1..close], 16).map_err(|_| format!("malformed unicode escape in {}", text))?;
+                chars = rest[close + 1..].chars();
+                match std::char::from_u32(code) {
+                    Some(c) => c,
+                    None => return Err(format!("invalid code point {:#x} in {}", code, text))
+                }
+            },
+            Some(other) => return Err(format!("unknown escape \\{} in {}", other, text)),
+            None => return Err(unterminated())
+        };
+        result.push(escaped);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::runtime::VM;
+    use crate::disassembler::disassemble;
+    use super::*;
+
+    // Assembles `source` into a fresh VM and disassembles it again.
+    fn listing(source: &str) -> Result<String, AssemblyError> {
+        let vm = &mut VM::new();
+        let chunk = assemble(source, &mut vm.string_pool, &mut vm.type_registry)?;
+        Ok(disassemble(&chunk, &vm.type_registry))
+    }
+
+    #[test]
+    fn listings_reassemble_to_themselves() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "sasm") {
+                let first = listing(&fs::read_to_string(&path).unwrap()).unwrap();
+                let second = listing(&first).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+                assert_eq!(first, second, "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        let error = listing(".const one 1i32\n\n    get_const one\n    frobnicate\n").unwrap_err();
+        assert_eq!(error.line, 4);
+        let error = listing("    get_const missing\n").unwrap_err();
+        assert_eq!(error.line, 1);
+    }
+}
@@ -0,0 +1,1080 @@
+// Reading and writing of compiled chunks in the Silicon bytecode file format.
+//
+// All integers are little-endian. A file is laid out as:
+//
+//   magic        4 bytes, "SILC"
+//   version      u16
+//   type table   u16 count, then `count` strings holding canonical type names
+//   const table  u16 count, then `count` entries of u16 slot, u8 tag and payload
+//   jump table   u16 count, then `count` entries of u16 label and u32 offset
+//   handlers     u16 count, then `count` entries of u16 index, u16 label, and a
+//                bool followed by a type operand if the handler has a catch type
+//   switches     u16 count, then `count` entries of u16 index, u16 default
+//                label, and either a u8 0, i128 first key and u16 count of
+//                labels for a dense table, or a u8 1 and u16 count of keys and
+//                labels for a sparse one. Keys are a u8 tag of 0 for an i128,
+//                1 for a u32 char or 2 for a string, followed by the value
+//   op stream    u32 count, then `count` entries of u8 tag and operands
+//
+// Strings are stored as a u32 byte length followed by UTF-8 data. Type operands
+// in the op stream index into the file's own type table rather than into a
+// `TypeRegistry`, since registry indices are only meaningful inside one VM.
+// Parameterised types are stored under their full names, such as
+// `silicon.lang.Array<silicon.lang.Int32>`, and reified again when read. A
+// class constant follows the slot of each of its parents with the type
+// arguments it passes to it: a u8 count, then for each either a u8 0 and the
+// u8 index of one of the class's type parameters, a u8 1 and a type operand,
+// or a u8 2, the type operand of a generic type and its arguments in turn.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use crate::instance::{Instance, Function, FunctionBody, Type, Class, Field, Capture, TypeArg};
+use crate::opcode::{Chunk, OpCode, Handler, SwitchTable, SwitchCases, SwitchKey};
+use crate::error::ErrorKind;
+use crate::runtime::TypeRegistry;
+use crate::string_pool::StringPool;
+use crate::verifier;
+use crate::verifier::VerifyError;
+use crate::decimal::{Decimal, DecimalRepr, RoundingMode, MAX_SCALE};
+
+pub const MAGIC: &[u8; 4] = b"SILC";
+pub const VERSION: u16 = 5;
+
+// How deeply functions, type arguments and type names may be nested in a file.
+// Each level is read recursively, so without a limit a crafted file could
+// exhaust the stack.
+pub(crate) const MAX_NESTING: usize = 64;
+
+#[derive(Debug)]
+pub enum BytecodeError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEof(usize),
+    UnknownConstantTag(u8),
+    UnknownOpCode(u8),
+    InvalidUtf8(usize),
+    InvalidChar(u32),
+    TypeIndexOutOfRange(u16),
+    UnknownType(String),
+    DuplicateType(String),
+    NotAClass(u16),
+    InvalidType(String),
+    InvalidScale(u8),
+    UnknownRoundingMode(u8),
+    UnknownCaptureKind(u8),
+    UnknownTypeArgKind(u8),
+    UnknownSwitchKind(u8),
+    UnknownSwitchKey(u8),
+    SwitchOutOfRange(i128, u16),
+    Unverified(VerifyError),
+    JumpOutOfRange(u16, usize),
+    TrailingBytes(usize),
+    TooDeeplyNested(usize),
+    UnsupportedConstant(u16),
+    TooLarge,
+}
+
+impl Display for BytecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BytecodeError::BadMagic => write!(f, "not a Silicon bytecode file"),
+            BytecodeError::UnsupportedVersion(version) => write!(f, "unsupported bytecode version {}", version),
+            BytecodeError::UnexpectedEof(pos) => write!(f, "unexpected end of file at byte {}", pos),
+            BytecodeError::UnknownConstantTag(tag) => write!(f, "unknown constant tag {}", tag),
+            BytecodeError::UnknownOpCode(tag) => write!(f, "unknown op code {}", tag),
+            BytecodeError::InvalidUtf8(pos) => write!(f, "invalid UTF-8 in string at byte {}", pos),
+            BytecodeError::InvalidChar(code) => write!(f, "invalid char code point {:#x}", code),
+            BytecodeError::TypeIndexOutOfRange(index) => write!(f, "type table index {} is out of range", index),
+            BytecodeError::UnknownType(name) => write!(f, "type {} does not exist", name),
+            BytecodeError::DuplicateType(name) => write!(f, "type {} is already registered", name),
+            BytecodeError::InvalidType(message) => write!(f, "invalid type: {}", message),
+            BytecodeError::InvalidScale(scale) => write!(f, "decimal scale {} is above the maximum of {}", scale, MAX_SCALE),
+            BytecodeError::UnknownRoundingMode(tag) => write!(f, "unknown rounding mode {}", tag),
+            BytecodeError::UnknownCaptureKind(kind) => write!(f, "unknown capture kind {}", kind),
+            BytecodeError::UnknownTypeArgKind(kind) => write!(f, "unknown type argument kind {}", kind),
+            BytecodeError::UnknownSwitchKind(kind) => write!(f, "unknown switch table kind {}", kind),
+            BytecodeError::UnknownSwitchKey(tag) => write!(f, "unknown switch key tag {}", tag),
+            BytecodeError::SwitchOutOfRange(first, count) => write!(f, "dense switch table of {} cases from {} runs past the largest key", count, first),
+            BytecodeError::Unverified(error) => write!(f, "{}", error),
+            BytecodeError::NotAClass(index) => write!(f, "constant slot {} is not a previously defined class", index),
+            BytecodeError::JumpOutOfRange(label, offset) => write!(f, "jump label {} points past the end of the chunk ({})", label, offset),
+            BytecodeError::TrailingBytes(pos) => write!(f, "unexpected data after the op stream at byte {}", pos),
+            BytecodeError::TooDeeplyNested(pos) => write!(f, "nested more than {} levels deep at byte {}", MAX_NESTING, pos),
+            BytecodeError::UnsupportedConstant(index) => write!(f, "constant slot {} cannot be serialized", index),
+            BytecodeError::TooLarge => write!(f, "chunk is too large to serialize"),
+        }
+    }
+}
+
+impl std::error::Error for BytecodeError {}
+
+pub fn write_chunk(chunk: &Chunk, type_registry: &TypeRegistry) -> Result<Vec<u8>, BytecodeError> {
+    let mut writer = Writer::new();
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.write_u16(VERSION);
+
+    // Collect every type the chunk refers to so they can be stored by name.
+    writer.collect_types(chunk, type_registry)?;
+    writer.write_u16(to_u16(writer.type_table.len())?);
+    for name in writer.type_table.clone() {
+        writer.write_str(&name)?;
+    }
+
+    writer.write_body(chunk)?;
+    Ok(writer.bytes)
+}
+
+// Reads a chunk, registering any classes it defines in `type_registry`, and
+// verifies it so that it is safe to run.
+pub fn read_chunk(bytes: &[u8], string_pool: &mut StringPool, type_registry: &mut TypeRegistry) -> Result<Chunk, BytecodeError> {
+    // Types are registered in a copy of the registry while the file is read,
+    // since its members may refer to them, and only kept once all of it has
+    // been read and verified, so that a file that fails leaves none behind.
+    let mut staged = type_registry.clone();
+    let chunk = read_staged(bytes, string_pool, &mut staged)?;
+    *type_registry = staged;
+    Ok(chunk)
+}
+
+fn read_staged(bytes: &[u8], string_pool: &mut StringPool, type_registry: &mut TypeRegistry) -> Result<Chunk, BytecodeError> {
+    let mut reader = Reader::new(bytes, string_pool, type_registry);
+    if reader.read_bytes(MAGIC.len())? != MAGIC {
+        return Err(BytecodeError::BadMagic)
+    }
+    let version = reader.read_u16()?;
+    if version != VERSION {
+        return Err(BytecodeError::UnsupportedVersion(version))
+    }
+
+    // Names are resolved as they are used, since some may refer to classes
+    // defined further on in the file.
+    let type_count = reader.read_u16()?;
+    for _ in 0..type_count {
+        let name = reader.read_string()?;
+        let name = reader.string_pool.pool_string(name);
+        reader.type_table.push(name);
+    }
+
+    let chunk = reader.read_body()?;
+    if reader.pos != bytes.len() {
+        return Err(BytecodeError::TrailingBytes(reader.pos))
+    }
+    verifier::verify(&chunk).map_err(BytecodeError::Unverified)?;
+    Ok(chunk)
+}
+
+// Counts and lengths are checked before they are narrowed, so that a chunk too
+// large for the format fails to serialize rather than writing a wrong count.
+fn to_u8(value: usize) -> Result<u8, BytecodeError> {
+    u8::try_from(value).map_err(|_| BytecodeError::TooLarge)
+}
+
+fn to_u16(value: usize) -> Result<u16, BytecodeError> {
+    u16::try_from(value).map_err(|_| BytecodeError::TooLarge)
+}
+
+fn to_u32(value: usize) -> Result<u32, BytecodeError> {
+    u32::try_from(value).map_err(|_| BytecodeError::TooLarge)
+}
+
+// Finds the slot `class` occupies in `chunk`, which has to come before the slot
+// of the class at `slot` that refers to it so that the reader has seen it.
+fn find_class_slot(slot: u16, class: &Rc<Class>, chunk: &Chunk) -> Result<u16, BytecodeError> {
+    let found = chunk.const_table.iter().find_map(|(other, constant)| match constant {
+        Instance::Class(other_class) if Rc::ptr_eq(other_class, class) && *other < slot => Some(*other),
+        _ => None
+    });
+    found.ok_or(BytecodeError::UnsupportedConstant(slot))
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    type_table: Vec<Rc<String>>,
+    type_slots: HashMap<Rc<String>, u16>,
+    type_names: HashMap<u16, Rc<String>>
+}
+
+impl Writer {
+    fn new() -> Writer {
+        Writer {
+            bytes: vec![],
+            type_table: vec![],
+            type_slots: HashMap::new(),
+            type_names: HashMap::new()
+        }
+    }
+
+    fn collect_type(&mut self, name: Rc<String>) {
+        if !self.type_slots.contains_key(&name) {
+            self.type_slots.insert(Rc::clone(&name), self.type_table.len() as u16);
+            self.type_table.push(name);
+        }
+    }
+
+    fn collect_types(&mut self, chunk: &Chunk, type_registry: &TypeRegistry) -> Result<(), BytecodeError> {
+        for op in &chunk.op_codes {
+            if let OpCode::Declare(_, type_index) | OpCode::Is(type_index) | OpCode::Convert(type_index) | OpCode::InitArray(_, type_index) | OpCode::NewGeneric(type_index) = op {
+                match type_registry.try_get(*type_index) {
+                    Some(_type) => {
+                        self.type_names.insert(*type_index, _type.get_canonical_name());
+                        self.collect_type(_type.get_canonical_name())
+                    },
+                    None => return Err(BytecodeError::UnknownType(type_index.to_string()))
+                }
+            }
+        }
+        for type_index in chunk.handler_table.values().filter_map(|handler| handler.catch_type) {
+            match type_registry.try_get(type_index) {
+                Some(_type) => {
+                    self.type_names.insert(type_index, _type.get_canonical_name());
+                    self.collect_type(_type.get_canonical_name())
+                },
+                None => return Err(BytecodeError::UnknownType(type_index.to_string()))
+            }
+        }
+        for constant in chunk.const_table.values() {
+            match constant {
+                Instance::Func(function) => self.collect_function_types(function, type_registry)?,
+                Instance::Class(class) => {
+                    let mut parent_args = class._type.get_supertype_args();
+                    for index in 0..class.interfaces.len() {
+                        parent_args.extend(class._type.get_interface_args(index));
+                    }
+                    self.collect_type_args(&parent_args);
+                    for field in &class.fields {
+                        self.collect_type(field._type.get_canonical_name());
+                    }
+                    for method in class.methods.values() {
+                        self.collect_function_types(method, type_registry)?;
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_type_args(&mut self, args: &[TypeArg]) {
+        for arg in args {
+            match arg {
+                TypeArg::Param(_) => {},
+                TypeArg::Concrete(_type) => self.collect_type(_type.get_canonical_name()),
+                TypeArg::Applied(generic, args) => {
+                    self.collect_type(generic.get_canonical_name());
+                    self.collect_type_args(args)
+                }
+            }
+        }
+    }
+
+    fn collect_function_types(&mut self, function: &Function, type_registry: &TypeRegistry) -> Result<(), BytecodeError> {
+        for _type in &function.param_types {
+            self.collect_type(_type.get_canonical_name());
+        }
+        self.collect_type(function.return_type.get_canonical_name());
+        if let FunctionBody::Bytecode(chunk) = &function.body {
+            self.collect_types(chunk, type_registry)?;
+        }
+        Ok(())
+    }
+
+    fn write_body(&mut self, chunk: &Chunk) -> Result<(), BytecodeError> {
+        let mut slots: Vec<&u16> = chunk.const_table.keys().collect();
+        slots.sort();
+        self.write_u16(to_u16(slots.len())?);
+        for slot in slots {
+            self.write_u16(*slot);
+            self.write_const(*slot, &chunk.const_table[slot], chunk)?;
+        }
+
+        let mut labels: Vec<&u16> = chunk.jump_table.keys().collect();
+        labels.sort();
+        self.write_u16(to_u16(labels.len())?);
+        for label in labels {
+            self.write_u16(*label);
+            self.write_u32(to_u32(chunk.jump_table[label])?);
+        }
+
+        let mut handlers: Vec<&u16> = chunk.handler_table.keys().collect();
+        handlers.sort();
+        self.write_u16(to_u16(handlers.len())?);
+        for index in handlers {
+            let handler = chunk.handler_table[index];
+            self.write_u16(*index);
+            self.write_u16(handler.label);
+            self.write_bool(handler.catch_type.is_some());
+            if let Some(type_index) = handler.catch_type {
+                self.write_type_index(type_index);
+            }
+        }
+
+        let mut switches: Vec<&u16> = chunk.switch_table.keys().collect();
+        switches.sort();
+        self.write_u16(to_u16(switches.len())?);
+        for index in switches {
+            self.write_u16(*index);
+            self.write_switch(&chunk.switch_table[index])?;
+        }
+
+        self.write_u32(to_u32(chunk.op_codes.len())?);
+        for op in &chunk.op_codes {
+            self.write_op(op);
+        }
+        Ok(())
+    }
+
+    fn write_switch(&mut self, table: &SwitchTable) -> Result<(), BytecodeError> {
+        self.write_u16(table.default);
+        match &table.cases {
+            SwitchCases::Dense { first, labels } => {
+                self.write_u8(0);
+                self.bytes.extend_from_slice(&first.to_le_bytes());
+                self.write_u16(u16::try_from(labels.len()).map_err(|_| BytecodeError::TooLarge)?);
+                for label in labels {
+                    self.write_u16(*label);
+                }
+            },
+            SwitchCases::Sparse(cases) => {
+                // Sorted so that the same table always serializes the same way.
+                let mut cases: Vec<(&SwitchKey, &u16)> = cases.iter().collect();
+                cases.sort();
+                self.write_u8(1);
+                self.write_u16(u16::try_from(cases.len()).map_err(|_| BytecodeError::TooLarge)?);
+                for (key, label) in cases {
+                    match key {
+                        SwitchKey::Int(value) => { self.write_u8(0); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+                        SwitchKey::Char(c) => { self.write_u8(1); self.write_u32(*c as u32) },
+                        SwitchKey::Str(string) => { self.write_u8(2); self.write_str(string)? }
+                    }
+                    self.write_u16(*label);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_type(&mut self, _type: &Type) {
+        self.write_u16(self.type_slots[&_type.get_canonical_name()])
+    }
+
+    fn write_type_args(&mut self, args: &[TypeArg]) -> Result<(), BytecodeError> {
+        self.write_u8(to_u8(args.len())?);
+        for arg in args {
+            match arg {
+                TypeArg::Param(index) => { self.write_u8(0); self.write_u8(to_u8(*index)?) },
+                TypeArg::Concrete(_type) => { self.write_u8(1); self.write_type(_type) },
+                TypeArg::Applied(generic, args) => {
+                    self.write_u8(2);
+                    self.write_type(generic);
+                    self.write_type_args(args)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_type_index(&mut self, type_index: u16) {
+        self.write_u16(self.type_slots[&self.type_names[&type_index]])
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value)
+    }
+
+    fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8)
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes())
+    }
+
+    fn write_str(&mut self, value: &str) -> Result<(), BytecodeError> {
+        self.write_bytes(value.as_bytes())
+    }
+
+    fn write_bytes(&mut self, value: &[u8]) -> Result<(), BytecodeError> {
+        self.write_u32(to_u32(value.len())?);
+        self.bytes.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn write_const(&mut self, slot: u16, constant: &Instance, chunk: &Chunk) -> Result<(), BytecodeError> {
+        match constant {
+            Instance::Bool(value) => { self.write_u8(0); self.write_bool(*value) },
+            Instance::Byte(value) => { self.write_u8(1); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::UByte(value) => { self.write_u8(2); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::Int16(value) => { self.write_u8(3); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::UInt16(value) => { self.write_u8(4); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::Int32(value) => { self.write_u8(5); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::UInt32(value) => { self.write_u8(6); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::Int64(value) => { self.write_u8(7); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::UInt64(value) => { self.write_u8(8); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::Int128(value) => { self.write_u8(9); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::UInt128(value) => { self.write_u8(10); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::Float32(value) => { self.write_u8(11); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            Instance::Float64(value) => { self.write_u8(12); self.bytes.extend_from_slice(&value.to_le_bytes()) },
+            // Big integers are stored as a u32 byte length and little-endian
+            // bytes, in two's complement for BigInt.
+            Instance::BigInt(value) => { self.write_u8(18); self.write_bytes(&value.to_signed_bytes_le())? },
+            Instance::UBigInt(value) => { self.write_u8(19); self.write_bytes(&value.to_bytes_le())? },
+            Instance::Complex(value) => { self.write_u8(28); self.bytes.extend_from_slice(&value.re.to_le_bytes()); self.bytes.extend_from_slice(&value.im.to_le_bytes()) },
+            // Decimals are stored as a u8 scale and their unscaled value.
+            Instance::Decimal16(value) => { self.write_u8(20); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::UDecimal16(value) => { self.write_u8(21); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::Decimal32(value) => { self.write_u8(22); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::UDecimal32(value) => { self.write_u8(23); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::Decimal64(value) => { self.write_u8(24); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::UDecimal64(value) => { self.write_u8(25); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::Decimal128(value) => { self.write_u8(26); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::UDecimal128(value) => { self.write_u8(27); self.write_u8(value.get_scale()); self.bytes.extend_from_slice(&value.get_value().to_le_bytes()) },
+            Instance::Char(value) => { self.write_u8(13); self.write_u32(*value as u32) },
+            Instance::Str(value) => { self.write_u8(14); self.write_str(value)? },
+            Instance::Void => self.write_u8(15),
+            Instance::Func(function) => {
+                self.write_u8(16);
+                self.write_function(slot, function)?
+            },
+            Instance::Class(class) => {
+                self.write_u8(17);
+                self.write_str(&class._type.get_canonical_name())?;
+                self.write_u8(to_u8(class._type.get_type_param_count())?);
+                self.write_bool(class.is_interface);
+                self.write_bool(class.superclass.is_some());
+                if let Some(superclass) = &class.superclass {
+                    self.write_u16(find_class_slot(slot, superclass, chunk)?);
+                    self.write_type_args(&class._type.get_supertype_args())?;
+                }
+                self.write_u16(to_u16(class.interfaces.len())?);
+                for (index, interface) in class.interfaces.iter().enumerate() {
+                    self.write_u16(find_class_slot(slot, interface, chunk)?);
+                    self.write_type_args(&class._type.get_interface_args(index))?;
+                }
+                self.write_u16(to_u16(class.fields.len())?);
+                for field in &class.fields {
+                    self.write_str(&field.name)?;
+                    self.write_bool(field.is_const);
+                    self.write_type(&field._type);
+                }
+
+                // Sorted so that the same class always serializes the same way.
+                let mut names: Vec<&Rc<String>> = class.methods.keys().collect();
+                names.sort();
+                self.write_u16(to_u16(names.len())?);
+                for name in names {
+                    self.write_str(name)?;
+                    self.write_function(slot, &class.methods[name])?
+                }
+            },
+            Instance::Array(_, _) | Instance::ClassInstance(_) | Instance::Coroutine(_) => return Err(BytecodeError::UnsupportedConstant(slot))
+        }
+        Ok(())
+    }
+
+    fn write_function(&mut self, slot: u16, function: &Function) -> Result<(), BytecodeError> {
+        let chunk = match &function.body {
+            FunctionBody::Bytecode(chunk) => chunk,
+            FunctionBody::Native(_) => return Err(BytecodeError::UnsupportedConstant(slot))
+        };
+        self.write_u16(to_u16(function.param_types.len())?);
+        for _type in &function.param_types {
+            self.write_type(_type);
+        }
+        self.write_type(&function.return_type);
+        self.write_u16(to_u16(function.captures.len())?);
+        for capture in &function.captures {
+            match capture {
+                Capture::Local(slot) => { self.write_u8(0); self.write_u16(*slot) },
+                Capture::Captured(index) => { self.write_u8(1); self.write_u16(*index) }
+            }
+        }
+        self.write_body(chunk)
+    }
+
+    fn write_op(&mut self, op: &OpCode) {
+        match op {
+            OpCode::GetTrue => self.write_u8(0),
+            OpCode::GetFalse => self.write_u8(1),
+            OpCode::Get(from_const, index) => { self.write_u8(2); self.write_bool(*from_const); self.write_u16(*index) },
+            OpCode::Declare(is_const, type_index) => { self.write_u8(3); self.write_bool(*is_const); self.write_type_index(*type_index) },
+            OpCode::Set(index) => { self.write_u8(4); self.write_u16(*index) },
+            OpCode::Add => self.write_u8(5),
+            OpCode::Subtract => self.write_u8(6),
+            OpCode::Multiply => self.write_u8(7),
+            OpCode::Divide => self.write_u8(8),
+            OpCode::Power => self.write_u8(9),
+            OpCode::IntNegate => self.write_u8(10),
+            OpCode::LogicNegate => self.write_u8(11),
+            OpCode::Less => self.write_u8(12),
+            OpCode::Greater => self.write_u8(13),
+            OpCode::LessOrEq => self.write_u8(14),
+            OpCode::GreaterOrEq => self.write_u8(15),
+            OpCode::Eq => self.write_u8(16),
+            OpCode::NotEq => self.write_u8(17),
+            OpCode::Is(type_index) => { self.write_u8(18); self.write_type_index(*type_index) },
+            OpCode::Concat => self.write_u8(19),
+            OpCode::Format(index, count) => { self.write_u8(44); self.write_u16(*index); self.write_u8(*count) },
+            OpCode::Jump(conditional, label) => { self.write_u8(20); self.write_bool(*conditional); self.write_u16(*label) },
+            OpCode::JumpIfTrue(label) => { self.write_u8(45); self.write_u16(*label) },
+            OpCode::Call => self.write_u8(21),
+            OpCode::Return(with_value) => { self.write_u8(22); self.write_bool(*with_value) },
+            OpCode::InitArray(size, type_index) => { self.write_u8(23); self.write_u16(*size); self.write_type_index(*type_index) },
+            OpCode::IndexGet => self.write_u8(24),
+            OpCode::IndexSet => self.write_u8(25),
+            OpCode::EnterScope(size) => { self.write_u8(26); self.write_u16(*size) },
+            OpCode::ExitScope => self.write_u8(27),
+            OpCode::Print => self.write_u8(28),
+            OpCode::GetNative(index) => { self.write_u8(29); self.write_u16(*index) },
+            OpCode::New => self.write_u8(30),
+            OpCode::GetField(index) => { self.write_u8(31); self.write_u16(*index) },
+            OpCode::SetField(index) => { self.write_u8(32); self.write_u16(*index) },
+            OpCode::Invoke(index) => { self.write_u8(33); self.write_u16(*index) },
+            OpCode::Closure(index) => { self.write_u8(49); self.write_u16(*index) },
+            OpCode::GetCaptured(index) => { self.write_u8(50); self.write_u16(*index) },
+            OpCode::SetCaptured(index) => { self.write_u8(51); self.write_u16(*index) },
+            OpCode::Throw => self.write_u8(46),
+            OpCode::Try(index) => { self.write_u8(47); self.write_u16(*index) },
+            OpCode::EndTry => self.write_u8(48),
+            OpCode::Switch(index) => { self.write_u8(54); self.write_u16(*index) },
+            OpCode::Yield => self.write_u8(52),
+            OpCode::Resume => self.write_u8(53),
+            OpCode::NewGeneric(type_index) => { self.write_u8(34); self.write_type_index(*type_index) },
+            OpCode::Convert(type_index) => { self.write_u8(35); self.write_type_index(*type_index) },
+            OpCode::Modulo => self.write_u8(36),
+            OpCode::BitAnd => self.write_u8(37),
+            OpCode::BitOr => self.write_u8(38),
+            OpCode::BitXor => self.write_u8(39),
+            OpCode::BitNot => self.write_u8(40),
+            OpCode::ShiftLeft => self.write_u8(41),
+            OpCode::ShiftRight => self.write_u8(42),
+            OpCode::DivideRounded(scale, mode) => { self.write_u8(43); self.write_u8(*scale); self.write_u8(mode.to_tag()) },
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    type_table: Vec<Rc<String>>,
+    string_pool: &'a mut StringPool,
+    type_registry: &'a mut TypeRegistry,
+    depth: usize
+}
+
+impl <'a> Reader<'a> {
+    fn new(bytes: &'a [u8], string_pool: &'a mut StringPool, type_registry: &'a mut TypeRegistry) -> Reader<'a> {
+        Reader {
+            bytes,
+            pos: 0,
+            type_table: vec![],
+            string_pool,
+            type_registry,
+            depth: 0
+        }
+    }
+
+    // Reads something that may hold more of itself, such as the body of a
+    // function, one level further down.
+    fn read_nested<T, F>(&mut self, read: F) -> Result<T, BytecodeError>
+        where F: FnOnce(&mut Reader<'a>) -> Result<T, BytecodeError>
+    {
+        if self.depth == MAX_NESTING {
+            return Err(BytecodeError::TooDeeplyNested(self.pos))
+        }
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn read_body(&mut self) -> Result<Chunk, BytecodeError> {
+        let mut chunk = Chunk::new();
+
+        let const_count = self.read_u16()?;
+        for _ in 0..const_count {
+            let slot = self.read_u16()?;
+            let constant = self.read_const(&chunk)?;
+            chunk.add_const(slot, constant);
+        }
+
+        let label_count = self.read_u16()?;
+        let mut jump_table: HashMap<u16, usize> = HashMap::new();
+        for _ in 0..label_count {
+            let label = self.read_u16()?;
+            let offset = self.read_u32()? as usize;
+            jump_table.insert(label, offset);
+        }
+
+        let handler_count = self.read_u16()?;
+        for _ in 0..handler_count {
+            let index = self.read_u16()?;
+            let label = self.read_u16()?;
+            let catch_type = match self.read_bool()? {
+                true => Some(self.read_type_index()?),
+                false => None
+            };
+            chunk.handler_table.insert(index, Handler { label, catch_type });
+        }
+
+        let switch_count = self.read_u16()?;
+        for _ in 0..switch_count {
+            let index = self.read_u16()?;
+            let table = self.read_switch()?;
+            chunk.switch_table.insert(index, table);
+        }
+
+        let op_count = self.read_u32()? as usize;
+        for _ in 0..op_count {
+            let op = self.read_op()?;
+            chunk.write(op);
+        }
+
+        for (label, offset) in &jump_table {
+            if *offset > op_count {
+                return Err(BytecodeError::JumpOutOfRange(*label, *offset))
+            }
+        }
+        chunk.jump_table = jump_table;
+        chunk.lock();
+
+        Ok(chunk)
+    }
+
+    fn read_switch(&mut self) -> Result<SwitchTable, BytecodeError> {
+        let default = self.read_u16()?;
+        let cases = match self.read_u8()? {
+            0 => {
+                let first = i128::from_le_bytes(self.read_array()?);
+                let count = self.read_u16()?;
+                if count > 0 && first.checked_add(count as i128 - 1).is_none() {
+                    return Err(BytecodeError::SwitchOutOfRange(first, count))
+                }
+                let labels = (0..count).map(|_| self.read_u16()).collect::<Result<Vec<u16>, BytecodeError>>()?;
+                SwitchCases::Dense { first, labels }
+            },
+            1 => {
+                let count = self.read_u16()?;
+                let mut cases = HashMap::new();
+                for _ in 0..count {
+                    let key = match self.read_u8()? {
+                        0 => SwitchKey::Int(i128::from_le_bytes(self.read_array()?)),
+                        1 => {
+                            let code = self.read_u32()?;
+                            SwitchKey::Char(std::char::from_u32(code).ok_or(BytecodeError::InvalidChar(code))?)
+                        },
+                        2 => {
+                            let string = self.read_string()?;
+                            SwitchKey::Str(self.string_pool.pool_string(string))
+                        },
+                        tag => return Err(BytecodeError::UnknownSwitchKey(tag))
+                    };
+                    cases.insert(key, self.read_u16()?);
+                }
+                SwitchCases::Sparse(cases)
+            },
+            kind => return Err(BytecodeError::UnknownSwitchKind(kind))
+        };
+        Ok(SwitchTable { default, cases })
+    }
+
+    fn read_bytes(&mut self, amount: usize) -> Result<&'a [u8], BytecodeError> {
+        if self.bytes.len() - self.pos < amount {
+            return Err(BytecodeError::UnexpectedEof(self.pos))
+        }
+        let slice = &self.bytes[self.pos..self.pos + amount];
+        self.pos += amount;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], BytecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, BytecodeError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_bool(&mut self) -> Result<bool, BytecodeError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, BytecodeError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, BytecodeError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_scale(&mut self) -> Result<u8, BytecodeError> {
+        match self.read_u8()? {
+            scale if scale <= MAX_SCALE => Ok(scale),
+            scale => Err(BytecodeError::InvalidScale(scale))
+        }
+    }
+
+    fn read_decimal<T: DecimalRepr, const N: usize>(&mut self, from_le_bytes: fn([u8; N]) -> T) -> Result<Decimal<T>, BytecodeError> {
+        let scale = self.read_scale()?;
+        Ok(Decimal::new(from_le_bytes(self.read_array()?), scale))
+    }
+
+    fn read_rounding_mode(&mut self) -> Result<RoundingMode, BytecodeError> {
+        let tag = self.read_u8()?;
+        match RoundingMode::ALL.get(tag as usize) {
+            Some(mode) => Ok(*mode),
+            None => Err(BytecodeError::UnknownRoundingMode(tag))
+        }
+    }
+
+    fn read_string(&mut self) -> Result<String, BytecodeError> {
+        let length = self.read_u32()? as usize;
+        let start = self.pos;
+        let bytes = self.read_bytes(length)?;
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(string) => Ok(string),
+            Err(_) => Err(BytecodeError::InvalidUtf8(start))
+        }
+    }
+
+    fn read_type_name(&mut self) -> Result<Rc<String>, BytecodeError> {
+        let slot = self.read_u16()?;
+        match self.type_table.get(slot as usize) {
+            Some(name) => Ok(Rc::clone(name)),
+            None => Err(BytecodeError::TypeIndexOutOfRange(slot))
+        }
+    }
+
+    fn read_type_index(&mut self) -> Result<u16, BytecodeError> {
+        let name = self.read_type_name()?;
+        match self.type_registry.resolve_name(&name) {
+            Ok(index) => Ok(index),
+            Err(ErrorKind::UnknownType(_)) => Err(BytecodeError::UnknownType(name.to_string())),
+            Err(ErrorKind::TypeTooDeeplyNested) => Err(BytecodeError::TooDeeplyNested(self.pos)),
+            Err(kind) => Err(BytecodeError::InvalidType(kind.to_string()))
+        }
+    }
+
+    fn read_type(&mut self) -> Result<Rc<Type>, BytecodeError> {
+        let index = self.read_type_index()?;
+        self.type_registry.get(index).map_err(|kind| BytecodeError::InvalidType(kind.to_string()))
+    }
+
+    fn read_type_args(&mut self) -> Result<Vec<TypeArg>, BytecodeError> {
+        let count = self.read_u8()?;
+        let mut args = vec![];
+        for _ in 0..count {
+            args.push(match self.read_u8()? {
+                0 => TypeArg::Param(self.read_u8()? as usize),
+                1 => TypeArg::Concrete(self.read_type()?),
+                2 => TypeArg::Applied(self.read_type()?, self.read_nested(Reader::read_type_args)?),
+                tag => return Err(BytecodeError::UnknownTypeArgKind(tag))
+            });
+        }
+        Ok(args)
+    }
+
+    fn read_function(&mut self) -> Result<Function, BytecodeError> {
+        let param_count = self.read_u16()?;
+        let mut param_types = vec![];
+        for _ in 0..param_count {
+            param_types.push(self.read_type()?);
+        }
+        let return_type = self.read_type()?;
+        let capture_count = self.read_u16()?;
+        let mut captures = vec![];
+        for _ in 0..capture_count {
+            captures.push(match self.read_u8()? {
+                0 => Capture::Local(self.read_u16()?),
+                1 => Capture::Captured(self.read_u16()?),
+                kind => return Err(BytecodeError::UnknownCaptureKind(kind))
+            });
+        }
+        let chunk = self.read_nested(Reader::read_body)?;
+        let mut function = Function::new(Rc::new(chunk), param_types, return_type);
+        function.captures = captures;
+        Ok(function)
+    }
+
+    // Registers the class's type before reading its members, which may refer
+    // to it. Superclasses and interfaces are earlier constants in `chunk`.
+    fn read_class(&mut self, chunk: &Chunk) -> Result<Class, BytecodeError> {
+        let name = self.read_string()?;
+        let name = self.string_pool.pool_string(name);
+        let type_param_count = self.read_u8()? as usize;
+        let is_interface = self.read_bool()?;
+        let superclass = match self.read_bool()? {
+            true => Some((self.read_class_slot(chunk)?, self.read_type_args()?)),
+            false => None
+        };
+        let interface_count = self.read_u16()?;
+        let mut interfaces = vec![];
+        for _ in 0..interface_count {
+            interfaces.push((self.read_class_slot(chunk)?, self.read_type_args()?));
+        }
+
+        let supertype = superclass.as_ref().map(|(superclass, args)| (Rc::clone(&superclass._type), args.clone()));
+        let interface_types = interfaces.iter().map(|(interface, args)| (Rc::clone(&interface._type), args.clone())).collect();
+        let _type = match self.type_registry.define_type(Rc::clone(&name), type_param_count, supertype, interface_types) {
+            Ok(_type) => _type,
+            Err(ErrorKind::DuplicateType(_)) => return Err(BytecodeError::DuplicateType(name.to_string())),
+            Err(kind) => return Err(BytecodeError::InvalidType(kind.to_string()))
+        };
+        let superclass = superclass.map(|(superclass, _)| superclass);
+        let interfaces = interfaces.into_iter().map(|(interface, _)| interface).collect();
+
+        let field_count = self.read_u16()?;
+        let mut fields = vec![];
+        for _ in 0..field_count {
+            let field_name = self.read_string()?;
+            let field_name = self.string_pool.pool_string(field_name);
+            let is_const = self.read_bool()?;
+            fields.push(Field::new(field_name, self.read_type()?, is_const));
+        }
+
+        let method_count = self.read_u16()?;
+        let mut methods = HashMap::new();
+        for _ in 0..method_count {
+            let method_name = self.read_string()?;
+            let method_name = self.string_pool.pool_string(method_name);
+            methods.insert(method_name, Rc::new(self.read_function()?));
+        }
+
+        if is_interface {
+            return Ok(Class::new_interface(_type, interfaces, methods))
+        }
+        Ok(Class::new(_type, superclass, interfaces, fields, methods))
+    }
+
+    fn read_class_slot(&mut self, chunk: &Chunk) -> Result<Rc<Class>, BytecodeError> {
+        let slot = self.read_u16()?;
+        match chunk.const_table.get(&slot) {
+            Some(Instance::Class(class)) => Ok(Rc::clone(class)),
+            _ => Err(BytecodeError::NotAClass(slot))
+        }
+    }
+
+    fn read_const(&mut self, chunk: &Chunk) -> Result<Instance, BytecodeError> {
+        let tag = self.read_u8()?;
+        let constant = match tag {
+            0 => Instance::Bool(self.read_bool()?),
+            1 => Instance::Byte(i8::from_le_bytes(self.read_array()?)),
+            2 => Instance::UByte(u8::from_le_bytes(self.read_array()?)),
+            3 => Instance::Int16(i16::from_le_bytes(self.read_array()?)),
+            4 => Instance::UInt16(u16::from_le_bytes(self.read_array()?)),
+            5 => Instance::Int32(i32::from_le_bytes(self.read_array()?)),
+            6 => Instance::UInt32(u32::from_le_bytes(self.read_array()?)),
+            7 => Instance::Int64(i64::from_le_bytes(self.read_array()?)),
+            8 => Instance::UInt64(u64::from_le_bytes(self.read_array()?)),
+            9 => Instance::Int128(i128::from_le_bytes(self.read_array()?)),
+            10 => Instance::UInt128(u128::from_le_bytes(self.read_array()?)),
+            11 => Instance::Float32(f32::from_le_bytes(self.read_array()?)),
+            12 => Instance::Float64(f64::from_le_bytes(self.read_array()?)),
+            13 => {
+                let code = self.read_u32()?;
+                match std::char::from_u32(code) {
+                    Some(c) => Instance::Char(c),
+                    None => return Err(BytecodeError::InvalidChar(code))
+                }
+            },
+            14 => {
+                let string = self.read_string()?;
+                Instance::Str(self.string_pool.pool_string(string))
+            },
+            15 => Instance::Void,
+            16 => Instance::Func(Rc::new(self.read_function()?)),
+            17 => Instance::Class(Rc::new(self.read_class(chunk)?)),
+            18 => {
+                let length = self.read_u32()? as usize;
+                Instance::BigInt(num_bigint::BigInt::from_signed_bytes_le(self.read_bytes(length)?))
+            },
+            19 => {
+                let length = self.read_u32()? as usize;
+                Instance::UBigInt(num_bigint::BigUint::from_bytes_le(self.read_bytes(length)?))
+            },
+            20 => Instance::Decimal16(self.read_decimal(i16::from_le_bytes)?),
+            21 => Instance::UDecimal16(self.read_decimal(u16::from_le_bytes)?),
+            22 => Instance::Decimal32(self.read_decimal(i32::from_le_bytes)?),
+            23 => Instance::UDecimal32(self.read_decimal(u32::from_le_bytes)?),
+            24 => Instance::Decimal64(self.read_decimal(i64::from_le_bytes)?),
+            25 => Instance::UDecimal64(self.read_decimal(u64::from_le_bytes)?),
+            26 => Instance::Decimal128(self.read_decimal(i128::from_le_bytes)?),
+            27 => Instance::UDecimal128(self.read_decimal(u128::from_le_bytes)?),
+            28 => Instance::Complex(num_complex::Complex64::new(f64::from_le_bytes(self.read_array()?), f64::from_le_bytes(self.read_array()?))),
+            _ => return Err(BytecodeError::UnknownConstantTag(tag))
+        };
+        Ok(constant)
+    }
+
+    fn read_op(&mut self) -> Result<OpCode, BytecodeError> {
+        let tag = self.read_u8()?;
+        let op = match tag {
+            0 => OpCode::GetTrue,
+            1 => OpCode::GetFalse,
+            2 => OpCode::Get(self.read_bool()?, self.read_u16()?),
+            3 => OpCode::Declare(self.read_bool()?, self.read_type_index()?),
+            4 => OpCode::Set(self.read_u16()?),
+            5 => OpCode::Add,
+            6 => OpCode::Subtract,
+            7 => OpCode::Multiply,
+            8 => OpCode::Divide,
+            9 => OpCode::Power,
+            10 => OpCode::IntNegate,
+            11 => OpCode::LogicNegate,
+            12 => OpCode::Less,
+            13 => OpCode::Greater,
+            14 => OpCode::LessOrEq,
+            15 => OpCode::GreaterOrEq,
+            16 => OpCode::Eq,
+            17 => OpCode::NotEq,
+            18 => OpCode::Is(self.read_type_index()?),
+            19 => OpCode::Concat,
+            20 => OpCode::Jump(self.read_bool()?, self.read_u16()?),
+            21 => OpCode::Call,
+            22 => OpCode::Return(self.read_bool()?),
+            23 => OpCode::InitArray(self.read_u16()?, self.read_type_index()?),
+            24 => OpCode::IndexGet,
+            25 => OpCode::IndexSet,
+            26 => OpCode::EnterScope(self.read_u16()?),
+            27 => OpCode::ExitScope,
+            28 => OpCode::Print,
+            29 => OpCode::GetNative(self.read_u16()?),
+            30 => OpCode::New,
+            31 => OpCode::GetField(self.read_u16()?),
+            32 => OpCode::SetField(self.read_u16()?),
+            33 => OpCode::Invoke(self.read_u16()?),
+            34 => OpCode::NewGeneric(self.read_type_index()?),
+            35 => OpCode::Convert(self.read_type_index()?),
+            36 => OpCode::Modulo,
+            37 => OpCode::BitAnd,
+            38 => OpCode::BitOr,
+            39 => OpCode::BitXor,
+            40 => OpCode::BitNot,
+            41 => OpCode::ShiftLeft,
+            42 => OpCode::ShiftRight,
+            43 => OpCode::DivideRounded(self.read_scale()?, self.read_rounding_mode()?),
+            44 => OpCode::Format(self.read_u16()?, self.read_u8()?),
+            45 => OpCode::JumpIfTrue(self.read_u16()?),
+            46 => OpCode::Throw,
+            47 => OpCode::Try(self.read_u16()?),
+            48 => OpCode::EndTry,
+            49 => OpCode::Closure(self.read_u16()?),
+            50 => OpCode::GetCaptured(self.read_u16()?),
+            51 => OpCode::SetCaptured(self.read_u16()?),
+            52 => OpCode::Yield,
+            53 => OpCode::Resume,
+            54 => OpCode::Switch(self.read_u16()?),
+            _ => return Err(BytecodeError::UnknownOpCode(tag))
+        };
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::runtime::VM;
+    use crate::assembler::assemble;
+    use crate::disassembler::disassemble;
+    use super::*;
+
+    // Assembles `source` and writes it out, along with its listing.
+    fn compile(source: &str) -> (Vec<u8>, String) {
+        let vm = &mut VM::new();
+        let chunk = assemble(source, &mut vm.string_pool, &mut vm.type_registry).unwrap();
+        (write_chunk(&chunk, &vm.type_registry).unwrap(), disassemble(&chunk, &vm.type_registry))
+    }
+
+    #[test]
+    fn samples_read_back_as_written() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "sasm") {
+                let (bytes, listing) = compile(&fs::read_to_string(&path).unwrap());
+                let vm = &mut VM::new();
+                let chunk = read_chunk(&bytes, &mut vm.string_pool, &mut vm.type_registry)
+                    .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+                assert_eq!(disassemble(&chunk, &vm.type_registry), listing, "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn failed_loads_register_no_types() {
+        let (bytes, _) = compile(".class Point silicon.test.Point\n    .field x silicon.lang.Int32\n.end\n\n    get_const Point\n    new\n    print\n");
+        let vm = &mut VM::new();
+        let name = vm.string_pool.pool_str("silicon.test.Point");
+        for length in [bytes.len() - 1, bytes.len() + 1] {
+            let mut broken = bytes.clone();
+            broken.resize(length, 0);
+            assert!(read_chunk(&broken, &mut vm.string_pool, &mut vm.type_registry).is_err());
+            assert!(vm.type_registry.get_index(&name).is_none());
+        }
+        read_chunk(&bytes, &mut vm.string_pool, &mut vm.type_registry).unwrap();
+        assert!(vm.type_registry.get_index(&name).is_some());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| {
+            let mut source = String::new();
+            for level in 0..depth {
+                source.push_str(&format!(".func f{} () -> silicon.lang.Void\n", level));
+            }
+            source.push_str("    return\n");
+            source.push_str(&".end\n".repeat(depth));
+            compile(&source).0
+        };
+        let vm = &mut VM::new();
+        assert!(read_chunk(&nested(MAX_NESTING - 1), &mut vm.string_pool, &mut vm.type_registry).is_ok());
+        assert!(matches!(read_chunk(&nested(MAX_NESTING + 1), &mut vm.string_pool, &mut vm.type_registry), Err(BytecodeError::TooDeeplyNested(_))));
+    }
+
+    #[test]
+    fn type_name_nesting_is_limited() {
+        let (bytes, _) = compile("    enter_scope 1\n    get_true\n    declare silicon.lang.Boolean\n    exit_scope\n");
+        let name = b"silicon.lang.Boolean";
+        let at = bytes.windows(name.len()).position(|window| window == name).unwrap() - 4;
+        let nested = |depth: usize| {
+            let nested_name = format!("{}silicon.lang.Boolean{}", "silicon.lang.Array<".repeat(depth), ">".repeat(depth));
+            let mut patched = bytes[..at].to_vec();
+            patched.extend_from_slice(&(nested_name.len() as u32).to_le_bytes());
+            patched.extend_from_slice(nested_name.as_bytes());
+            patched.extend_from_slice(&bytes[at + 4 + name.len()..]);
+            patched
+        };
+        let vm = &mut VM::new();
+        assert!(read_chunk(&nested(MAX_NESTING), &mut vm.string_pool, &mut vm.type_registry).is_ok());
+        for depth in [MAX_NESTING + 1, 100_000] {
+            assert!(matches!(read_chunk(&nested(depth), &mut vm.string_pool, &mut vm.type_registry), Err(BytecodeError::TooDeeplyNested(_))));
+        }
+    }
+
+    #[test]
+    fn unknown_switches_are_rejected() {
+        let (mut bytes, _) = compile(".switch S done\n.end\n.const zero 0i32\n\n    get_const zero\n    switch S\ndone:\n");
+        let at = bytes.windows(3).rposition(|window| window == [54, 0, 0]).unwrap();
+        bytes[at + 1..at + 3].copy_from_slice(&[0xff, 0xff]);
+        let vm = &mut VM::new();
+        assert!(matches!(read_chunk(&bytes, &mut vm.string_pool, &mut vm.type_registry), Err(BytecodeError::Unverified(_))));
+    }
+
+    #[test]
+    fn wrong_versions_are_rejected() {
+        let (mut bytes, _) = compile("    get_true\n    print\n");
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&[0xff, 0xff]);
+        let vm = &mut VM::new();
+        assert!(matches!(read_chunk(&bytes, &mut vm.string_pool, &mut vm.type_registry), Err(BytecodeError::UnsupportedVersion(0xffff))));
+        assert!(matches!(read_chunk(b"SILK", &mut vm.string_pool, &mut vm.type_registry), Err(BytecodeError::BadMagic)));
+    }
+}
@@ -0,0 +1,92 @@
+// Coroutines: functions that suspend themselves with `Yield` and carry on from
+// there when they are next resumed with `Resume`.
+//
+// Calls run on the Rust stack, so a suspended coroutine cannot be left running
+// underneath its resumer. Instead a `Yield` unwinds every frame of the
+// coroutine back to the `Resume` that ran it, noting where each one was, and
+// the coroutine takes the variables and operands those frames had in the
+// register and on the stack with it. Resuming puts them back on top of the
+// resumer's, wherever that now is, and re-enters the frames outermost first.
+//
+// Values pass both ways. The value given to the first `Resume` is the
+// function's argument, if it takes one, and the value given to each later one
+// is what the `Yield` that suspended the coroutine evaluates to. `Resume` in
+// turn evaluates to the value yielded or, once the function returns, to its
+// return value, after which the coroutine is done and cannot be resumed.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::instance::{Instance, Function, Variable};
+use crate::opcode::Chunk;
+use crate::runtime::CallFrame;
+
+pub struct Coroutine {
+    pub(crate) function: Rc<Function>,
+    pub(crate) state: RefCell<CoroutineState>
+}
+
+pub(crate) enum CoroutineState {
+    // Not resumed yet.
+    Created,
+    Suspended(Suspension),
+    Running,
+    Done
+}
+
+// What a coroutine takes with it when it yields.
+pub(crate) struct Suspension {
+    // Outermost first.
+    pub(crate) frames: Vec<SuspendedFrame>,
+    pub(crate) variables: Vec<Rc<RefCell<Variable>>>,
+    pub(crate) operands: Vec<Instance>,
+    // Where the variables and operands were in the register and on the stack,
+    // which the offsets in the frames are still relative to.
+    pub(crate) register_base: u16,
+    pub(crate) stack_base: usize
+}
+
+pub(crate) struct SuspendedFrame {
+    pub(crate) chunk: Rc<Chunk>,
+    pub(crate) frame: Rc<RefCell<CallFrame>>,
+    // The `Yield` or call the frame was running, which finishes when it is resumed.
+    pub(crate) pc: usize,
+    // The function running in the frame, if the frame before it called it.
+    pub(crate) call: Option<Rc<Function>>
+}
+
+impl Coroutine {
+    pub(crate) fn new(function: Rc<Function>) -> Coroutine {
+        Coroutine {
+            function,
+            state: RefCell::new(CoroutineState::Created)
+        }
+    }
+
+    pub fn status(&self) -> &'static str {
+        match &*self.state.borrow() {
+            CoroutineState::Created | CoroutineState::Suspended(_) => "suspended",
+            CoroutineState::Running => "running",
+            CoroutineState::Done => "done"
+        }
+    }
+
+    // The values the coroutine keeps alive, for the heap to trace.
+    pub(crate) fn held_values(&self) -> Vec<Instance> {
+        let mut values = vec![Instance::Func(Rc::clone(&self.function))];
+        if let CoroutineState::Suspended(suspension) = &*self.state.borrow() {
+            values.extend(suspension.operands.iter().cloned());
+            values.extend(suspension.variables.iter().map(|variable| variable.borrow().stored.to_owned()));
+            for suspended in &suspension.frames {
+                values.extend(suspended.frame.borrow().upvalues.iter().map(|variable| variable.borrow().stored.to_owned()));
+            }
+        }
+        values
+    }
+}
+
+impl fmt::Debug for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Coroutine({})", self.status())
+    }
+}
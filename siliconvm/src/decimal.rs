@@ -0,0 +1,388 @@
+// Fixed-point decimal numbers for Silicon VM
+//
+// A decimal is an integer `value` scaled down by `10^scale`, so 12.34 is
+// stored as 1234 with a scale of 2. Each decimal type is backed by the integer
+// type of the same width and signedness, and every value carries its own scale
+// of at most `MAX_SCALE` digits.
+//
+// Addition, subtraction and remainders are exact at the larger scale of the
+// two operands, and multiplication at the sum of both scales. Division rounds:
+// `Divide` to the larger scale of its operands using `RoundingMode::HalfEven`,
+// and `DivideRounded` to an explicit scale with an explicit rounding mode.
+// Raising a decimal to a power needs a whole, non-negative exponent.
+//
+// A result that does not fit in the backing integer, or whose scale is above
+// `MAX_SCALE`, is an error, or saturates at the type's bounds under
+// `OverflowPolicy::Saturate`. Decimals never wrap around.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
+use crate::error::ErrorKind;
+use crate::math::{MathExtensions, OverflowPolicy};
+
+pub const MAX_SCALE: u8 = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    // Towards zero.
+    Down,
+    // Away from zero.
+    Up,
+    Floor,
+    Ceiling,
+    // To the nearest neighbour, or as the suffix says when halfway between two.
+    HalfUp,
+    HalfDown,
+    HalfEven
+}
+
+impl RoundingMode {
+    pub const ALL: [RoundingMode; 7] = [RoundingMode::Down, RoundingMode::Up, RoundingMode::Floor, RoundingMode::Ceiling,
+        RoundingMode::HalfUp, RoundingMode::HalfDown, RoundingMode::HalfEven];
+
+    pub fn from_name(name: &str) -> Option<RoundingMode> {
+        RoundingMode::ALL.iter().copied().find(|mode| mode.name() == name)
+    }
+
+    // The mode's index in `ALL`, as stored in bytecode.
+    pub fn to_tag(self) -> u8 {
+        RoundingMode::ALL.iter().position(|mode| *mode == self).unwrap() as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RoundingMode::Down => "down",
+            RoundingMode::Up => "up",
+            RoundingMode::Floor => "floor",
+            RoundingMode::Ceiling => "ceiling",
+            RoundingMode::HalfUp => "half_up",
+            RoundingMode::HalfDown => "half_down",
+            RoundingMode::HalfEven => "half_even"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal<T> {
+    value: T,
+    scale: u8
+}
+
+impl <T> Decimal<T> where T: DecimalRepr {
+    // The scale is not checked, so it must be at most `MAX_SCALE`.
+    pub(crate) fn new(value: T, scale: u8) -> Decimal<T> {
+        Decimal { value, scale }
+    }
+
+    pub fn get_value(&self) -> T {
+        self.value
+    }
+
+    pub fn get_scale(&self) -> u8 {
+        self.scale
+    }
+
+    // Parses an optionally signed number such as `-12.50`, keeping every digit
+    // after the point in the scale.
+    pub fn parse(text: &str) -> Option<Decimal<T>> {
+        let (value, scale) = parse_unscaled(text)?;
+        Decimal::fit(value, scale)
+    }
+
+    pub(crate) fn to_unscaled(self) -> (BigInt, u8) {
+        (self.value.into(), self.scale)
+    }
+
+    // The decimal with the given unscaled value and scale, if it fits.
+    pub(crate) fn fit(value: BigInt, scale: u8) -> Option<Decimal<T>> {
+        if scale > MAX_SCALE {
+            return None
+        }
+        T::try_from(value).ok().map(|value| Decimal::new(value, scale))
+    }
+
+    // Like `fit`, applying `policy` to a result that does not.
+    fn resolve(value: BigInt, scale: u32, operation: &'static str, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let scale = u8::try_from(scale).unwrap_or(u8::MAX);
+        let overflow = || ErrorKind::IntegerOverflow { operation, type_name: Rc::new(T::DECIMAL_NAME.to_string()) };
+        if scale > MAX_SCALE {
+            return Err(overflow())
+        }
+        match Decimal::fit(value.clone(), scale) {
+            Some(decimal) => Ok(decimal),
+            None if policy == OverflowPolicy::Saturate => {
+                Ok(Decimal::new(if value.is_negative() { T::get_min() } else { T::get_max() }, scale))
+            },
+            None => Err(overflow())
+        }
+    }
+
+    pub(crate) fn add(self, other: Decimal<T>, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let (left, right, scale) = align(self.to_unscaled(), other.to_unscaled());
+        Decimal::resolve(left + right, scale as u32, "add", policy)
+    }
+
+    pub(crate) fn subtract(self, other: Decimal<T>, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let (left, right, scale) = align(self.to_unscaled(), other.to_unscaled());
+        Decimal::resolve(left - right, scale as u32, "subtract", policy)
+    }
+
+    pub(crate) fn multiply(self, other: Decimal<T>, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let scale = self.scale as u32 + other.scale as u32;
+        Decimal::resolve(self.value.into() * other.value.into(), scale, "multiply", policy)
+    }
+
+    pub(crate) fn divide(self, other: Decimal<T>, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        self.divide_to(other, self.scale.max(other.scale), RoundingMode::HalfEven, policy)
+    }
+
+    // Divides to exactly `scale` digits after the point, rounding with `mode`.
+    pub(crate) fn divide_to(self, other: Decimal<T>, scale: u8, mode: RoundingMode, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let (left, right): (BigInt, BigInt) = (self.value.into(), other.value.into());
+        if right.is_zero() {
+            return Err(ErrorKind::DivisionByZero)
+        }
+        // left / 10^ls / (right / 10^rs) * 10^scale, kept in whole numbers.
+        let shift = scale as i32 + other.scale as i32 - self.scale as i32;
+        let (numerator, denominator) = match shift >= 0 {
+            true => (left * power_of_ten(shift as u32), right),
+            false => (left, right * power_of_ten(-shift as u32))
+        };
+        Decimal::resolve(round_quotient(numerator, denominator, mode), scale as u32, "divide", policy)
+    }
+
+    pub(crate) fn remainder(self, other: Decimal<T>, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let (left, right, scale) = align(self.to_unscaled(), other.to_unscaled());
+        if right.is_zero() {
+            return Err(ErrorKind::DivisionByZero)
+        }
+        Decimal::resolve(left % right, scale as u32, "take the remainder of", policy)
+    }
+
+    pub(crate) fn power(self, exponent: Decimal<T>, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        let (value, scale) = exponent.to_unscaled();
+        if value.is_negative() {
+            return Err(ErrorKind::NegativeExponent)
+        }
+        let exponent = match to_whole(&value, scale).and_then(|whole| u32::try_from(whole).ok()) {
+            Some(exponent) => exponent,
+            None => return Err(ErrorKind::LossyConversion { value: Rc::new(format!("{}", exponent)), target: Rc::new("silicon.lang.UInt32".to_string()) })
+        };
+        // Trailing zeros are dropped first, so that exact powers such as
+        // `1.0 ^ 100` are not turned down for the scale they would have had.
+        let (base, scale) = normalize(self.value.into(), self.scale);
+        let scale = match (scale as u32).checked_mul(exponent) {
+            Some(scale) if scale <= MAX_SCALE as u32 => scale,
+            _ => return Err(ErrorKind::IntegerOverflow { operation: "raise", type_name: Rc::new(T::DECIMAL_NAME.to_string()) })
+        };
+        // Past `T::BITS`, any base but -1, 0 and 1 overflows, so the power is
+        // replaced by a value out of range with the same sign.
+        let result = match exponent > T::BITS && base.abs() > BigInt::from(1) {
+            true if base.is_negative() && exponent % 2 == 1 => -(BigInt::from(1) << T::BITS),
+            true => BigInt::from(1) << T::BITS,
+            false => base.pow(exponent)
+        };
+        Decimal::resolve(result, scale, "raise", policy)
+    }
+
+    pub(crate) fn negate(self, policy: OverflowPolicy) -> Result<Decimal<T>, ErrorKind> {
+        Decimal::resolve(-self.value.into(), self.scale as u32, "negate", policy)
+    }
+
+    pub(crate) fn compare(self, other: Decimal<T>) -> Ordering {
+        compare_unscaled(self.to_unscaled(), other.to_unscaled())
+    }
+}
+
+impl <T> Display for Decimal<T> where T: DecimalRepr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_unscaled(&self.value.into(), self.scale))
+    }
+}
+
+// The integer types that back a decimal type.
+pub(crate) trait DecimalRepr: MathExtensions + Into<BigInt> + TryFrom<BigInt> {
+    const DECIMAL_NAME: &'static str;
+}
+
+macro_rules! impl_decimal_repr {
+    ($($t:ty => $name:expr),*) => {
+        $(
+            impl DecimalRepr for $t {
+                const DECIMAL_NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+
+impl_decimal_repr!(
+    i16 => "silicon.lang.Decimal16",
+    u16 => "silicon.lang.UDecimal16",
+    i32 => "silicon.lang.Decimal32",
+    u32 => "silicon.lang.UDecimal32",
+    i64 => "silicon.lang.Decimal64",
+    u64 => "silicon.lang.UDecimal64",
+    i128 => "silicon.lang.Decimal128",
+    u128 => "silicon.lang.UDecimal128"
+);
+
+fn power_of_ten(exponent: u32) -> BigInt {
+    BigInt::from(10).pow(exponent)
+}
+
+// Drops the trailing zeros of an unscaled value's fractional part.
+fn normalize(mut value: BigInt, mut scale: u8) -> (BigInt, u8) {
+    let ten = BigInt::from(10);
+    while scale > 0 && (&value % &ten).is_zero() {
+        value /= &ten;
+        scale -= 1;
+    }
+    (value, scale)
+}
+
+// Brings two unscaled values to the larger of their scales.
+fn align(left: (BigInt, u8), right: (BigInt, u8)) -> (BigInt, BigInt, u8) {
+    let ((left, left_scale), (right, right_scale)) = (left, right);
+    match left_scale.cmp(&right_scale) {
+        Ordering::Less => (left * power_of_ten((right_scale - left_scale) as u32), right, right_scale),
+        Ordering::Greater => (left, right * power_of_ten((left_scale - right_scale) as u32), left_scale),
+        Ordering::Equal => (left, right, left_scale)
+    }
+}
+
+fn round_quotient(numerator: BigInt, denominator: BigInt, mode: RoundingMode) -> BigInt {
+    let quotient = &numerator / &denominator;
+    let remainder = &numerator % &denominator;
+    if remainder.is_zero() {
+        return quotient
+    }
+    let negative = numerator.is_negative() != denominator.is_negative();
+    let half = (remainder.abs() * BigInt::from(2)).cmp(&denominator.abs());
+    let away_from_zero = match mode {
+        RoundingMode::Down => false,
+        RoundingMode::Up => true,
+        RoundingMode::Floor => negative,
+        RoundingMode::Ceiling => !negative,
+        RoundingMode::HalfUp => half != Ordering::Less,
+        RoundingMode::HalfDown => half == Ordering::Greater,
+        RoundingMode::HalfEven => half == Ordering::Greater || (half == Ordering::Equal && quotient.bit(0))
+    };
+    match (away_from_zero, negative) {
+        (false, _) => quotient,
+        (true, false) => quotient + 1,
+        (true, true) => quotient - 1
+    }
+}
+
+// The value of an unscaled decimal as a whole number, if it has no fraction.
+pub(crate) fn to_whole(value: &BigInt, scale: u8) -> Option<BigInt> {
+    let divisor = power_of_ten(scale as u32);
+    match (value % &divisor).is_zero() {
+        true => Some(value / divisor),
+        false => None
+    }
+}
+
+pub(crate) fn parse_unscaled(text: &str) -> Option<(BigInt, u8)> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text.strip_prefix('+').unwrap_or(text))
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let digits = format!("{}{}", whole, fraction);
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) || fraction.len() > MAX_SCALE as usize {
+        return None
+    }
+    let value = BigInt::parse_bytes(digits.as_bytes(), 10)?;
+    Some((if negative { -value } else { value }, fraction.len() as u8))
+}
+
+pub(crate) fn format_unscaled(value: &BigInt, scale: u8) -> String {
+    let sign = if value.is_negative() { "-" } else { "" };
+    let digits = value.abs().to_string();
+    let scale = scale as usize;
+    if scale == 0 {
+        return format!("{}{}", sign, digits)
+    }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+// Formats an unscaled decimal with exactly `precision` digits after the point,
+// rounding half to even.
+pub(crate) fn format_with_precision(value: &BigInt, scale: u8, precision: u8) -> String {
+    let rescaled = match precision >= scale {
+        true => value * power_of_ten((precision - scale) as u32),
+        false => round_quotient(value.clone(), power_of_ten((scale - precision) as u32), RoundingMode::HalfEven)
+    };
+    format_unscaled(&rescaled, precision)
+}
+
+// Floats convert to and from decimals through their shortest representation,
+// so that 0.1f64 becomes 0.1 rather than the binary fraction closest to it.
+pub(crate) fn from_float(value: f64) -> Option<(BigInt, u8)> {
+    match value.is_finite() {
+        true => parse_unscaled(&value.to_string()),
+        false => None
+    }
+}
+
+pub(crate) fn to_float(value: &BigInt, scale: u8) -> f64 {
+    format_unscaled(value, scale).parse().unwrap_or(f64::NAN)
+}
+
+// Orders two unscaled decimals by value, whatever their scales.
+pub(crate) fn compare_unscaled(left: (BigInt, u8), right: (BigInt, u8)) -> Ordering {
+    let (left, right, _) = align(left, right);
+    left.cmp(&right)
+}
+
+// Orders an unscaled decimal against a finite float, taken as the decimal its
+// shortest representation spells out like `from_float` does, but with as many
+// digits after the point as that takes, even past `MAX_SCALE`.
+pub(crate) fn compare_with_float(value: (BigInt, u8), float: f64) -> Ordering {
+    let text = float.to_string();
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    let digits: BigInt = format!("{}{}", whole, fraction).parse().expect("finite floats print as digits");
+    let ((value, scale), float_scale) = (value, fraction.len() as u32);
+    match (scale as u32).cmp(&float_scale) {
+        Ordering::Less => (value * power_of_ten(float_scale - scale as u32)).cmp(&digits),
+        _ => value.cmp(&(digits * power_of_ten(scale as u32 - float_scale)))
+    }
+}
+
+// Whether two unscaled decimals have the same value, whatever their scales.
+pub(crate) fn same_value(left: (BigInt, u8), right: (BigInt, u8)) -> bool {
+    let (left, right, _) = align(left, right);
+    left == right
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raise(base: Decimal<i64>, exponent: i64) -> Result<(i64, u8), ErrorKind> {
+        let power = base.power(Decimal::new(exponent, 0), OverflowPolicy::Error)?;
+        Ok((power.get_value(), power.get_scale()))
+    }
+
+    #[test]
+    fn powers_drop_trailing_zeros_before_checking_the_scale() {
+        assert!(matches!(raise(Decimal::new(10, 1), 100), Ok((1, 0))));
+        assert!(matches!(raise(Decimal::new(250, 2), 2), Ok((625, 2))));
+        assert!(matches!(raise(Decimal::new(0, 2), 1000), Ok((0, 0))));
+    }
+
+    #[test]
+    fn powers_with_too_large_a_scale_overflow() {
+        assert!(matches!(raise(Decimal::new(105, 2), 3_000_000_000), Err(ErrorKind::IntegerOverflow { operation: "raise", .. })));
+        assert!(matches!(raise(Decimal::new(105, 2), 20), Err(ErrorKind::IntegerOverflow { operation: "raise", .. })));
+        assert!(matches!(raise(Decimal::new(105, 2), 4), Ok((121550625, 8))));
+    }
+}
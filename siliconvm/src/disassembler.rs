@@ -0,0 +1,270 @@
+// Turns chunks into a readable listing.
+//
+// Constants are listed first as `.const` lines named `k<slot>`, with functions
+// and classes written out as `.func` and `.class` blocks, followed by the
+// handler table as `.handler H<index>` lines and the switch table as `.switch
+// S<index>` blocks. Jump table entries are printed as
+// `L<label>:` lines ahead of the instruction they point at, and each
+// instruction is prefixed with its offset. The listing is valid
+// input for the assembler.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+use crate::instance::{Instance, Class, Function, FunctionBody, Capture, TypeArg};
+use crate::opcode::{Chunk, OpCode, SwitchCases, SwitchKey};
+use crate::runtime::TypeRegistry;
+
+pub fn disassemble(chunk: &Chunk, type_registry: &TypeRegistry) -> String {
+    let mut out = String::new();
+    disassemble_into(&mut out, chunk, type_registry, "");
+    out
+}
+
+fn disassemble_into(out: &mut String, chunk: &Chunk, type_registry: &TypeRegistry, indent: &str) {
+    let mut slots: Vec<&u16> = chunk.const_table.keys().collect();
+    slots.sort();
+    for slot in slots {
+        let constant = &chunk.const_table[slot];
+        match constant {
+            Instance::Func(function) if matches!(function.body, FunctionBody::Bytecode(_)) => {
+                disassemble_function(out, ".func", &format!("k{}", slot), function, type_registry, indent);
+            },
+            Instance::Class(class) => {
+                let directive = if class.is_interface { ".interface" } else { ".class" };
+                let mut header = format!("{} k{} {}", directive, slot, class._type.get_canonical_name());
+                let type_param_count = class._type.get_type_param_count();
+                if type_param_count > 0 {
+                    let type_params: Vec<String> = (1..=type_param_count).map(|i| format!("T{}", i)).collect();
+                    header.push_str(&format!("<{}>", type_params.join(",")));
+                }
+                if let Some(superclass) = &class.superclass {
+                    header.push_str(&format!(" extends {}", format_parent(superclass, &class._type.get_supertype_args(), chunk)));
+                }
+                if !class.interfaces.is_empty() {
+                    let interfaces: Vec<String> = class.interfaces.iter().enumerate()
+                        .map(|(index, interface)| format_parent(interface, &class._type.get_interface_args(index), chunk))
+                        .collect();
+                    let keyword = if class.is_interface { "extends" } else { "implements" };
+                    header.push_str(&format!(" {} {}", keyword, interfaces.join(", ")));
+                }
+                writeln!(out, "{}{}", indent, header).unwrap();
+                let inner = format!("{}    ", indent);
+                for field in &class.fields {
+                    let modifier = if field.is_const { "const " } else { "" };
+                    writeln!(out, "{}.field {}{} {}", inner, modifier, field.name, field._type.get_canonical_name()).unwrap();
+                }
+                let mut names: Vec<_> = class.methods.keys().collect();
+                names.sort();
+                for name in names {
+                    disassemble_function(out, ".method", name, &class.methods[name], type_registry, &inner);
+                }
+                writeln!(out, "{}.end", indent).unwrap();
+            },
+            _ => writeln!(out, "{}.const k{} {}", indent, slot, format_constant(constant)).unwrap()
+        }
+    }
+    let mut handlers: Vec<&u16> = chunk.handler_table.keys().collect();
+    handlers.sort();
+    for index in &handlers {
+        let handler = chunk.handler_table[index];
+        match handler.catch_type {
+            Some(type_index) => writeln!(out, "{}.handler H{} L{} {}", indent, index, handler.label, format_type(type_index, type_registry)).unwrap(),
+            None => writeln!(out, "{}.handler H{} L{}", indent, index, handler.label).unwrap()
+        }
+    }
+    let mut switches: Vec<&u16> = chunk.switch_table.keys().collect();
+    switches.sort();
+    for index in &switches {
+        let table = &chunk.switch_table[index];
+        writeln!(out, "{}.switch S{} L{}", indent, index, table.default).unwrap();
+        for (key, label) in table.cases() {
+            writeln!(out, "{}    .case {} L{}", indent, format_switch_key(&key), label).unwrap();
+        }
+        writeln!(out, "{}.end", indent).unwrap();
+    }
+    if !chunk.const_table.is_empty() || !handlers.is_empty() || !switches.is_empty() {
+        out.push('\n');
+    }
+
+    let mut labels: HashMap<usize, Vec<u16>> = HashMap::new();
+    for (label, offset) in &chunk.jump_table {
+        labels.entry(*offset).or_default().push(*label);
+    }
+    for at_offset in labels.values_mut() {
+        at_offset.sort();
+    }
+
+    for offset in 0..=chunk.op_codes.len() {
+        if let Some(at_offset) = labels.get(&offset) {
+            for label in at_offset {
+                writeln!(out, "{}L{}:", indent, label).unwrap();
+            }
+        }
+        if let Some(op) = chunk.op_codes.get(offset) {
+            let (text, comment) = format_op(op, chunk, type_registry);
+            match comment {
+                Some(comment) => writeln!(out, "{}{:04}    {:<32} ; {}", indent, offset, text, comment).unwrap(),
+                None => writeln!(out, "{}{:04}    {}", indent, offset, text).unwrap(),
+            }
+        }
+    }
+}
+
+fn disassemble_function(out: &mut String, directive: &str, name: &str, function: &Function, type_registry: &TypeRegistry, indent: &str) {
+    let params: Vec<String> = function.param_types.iter().map(|_type| _type.get_canonical_name().to_string()).collect();
+    let mut header = format!("{} {} ({}) -> {}", directive, name, params.join(", "), function.return_type.get_canonical_name());
+    if !function.captures.is_empty() {
+        let captures: Vec<String> = function.captures.iter().map(|capture| match capture {
+            Capture::Local(slot) => format!("r{}", slot),
+            Capture::Captured(index) => format!("c{}", index)
+        }).collect();
+        header.push_str(&format!(" captures {}", captures.join(", ")));
+    }
+    writeln!(out, "{}{}", indent, header).unwrap();
+    match &function.body {
+        FunctionBody::Bytecode(body) => disassemble_into(out, body, type_registry, &format!("{}    ", indent)),
+        FunctionBody::Native(_) => writeln!(out, "{}    ; native", indent).unwrap()
+    }
+    writeln!(out, "{}.end", indent).unwrap();
+}
+
+// Formats a constant so that it reads back to the same value, unlike `Display`
+// which does not escape strings or chars.
+pub fn format_constant(constant: &Instance) -> String {
+    match constant {
+        Instance::Char(c) => format!("{:?}", c),
+        Instance::Str(string) => format!("{:?}", string),
+        _ => format!("{}", constant)
+    }
+}
+
+fn format_switch_key(key: &SwitchKey) -> String {
+    match key {
+        SwitchKey::Int(value) => value.to_string(),
+        SwitchKey::Char(c) => format!("{:?}", c),
+        SwitchKey::Str(string) => format!("{:?}", string)
+    }
+}
+
+// Describes the switch table at `index` for the comment on a `switch`.
+fn format_switch(index: u16, chunk: &Chunk) -> String {
+    match chunk.switch_table.get(&index) {
+        Some(table) => {
+            let kind = match table.cases {
+                SwitchCases::Dense { .. } => "dense",
+                SwitchCases::Sparse(_) => "sparse"
+            };
+            format!("{}, {} case(s), default {}", kind, table.cases().len(), format_label(table.default, chunk))
+        },
+        None => "<missing switch>".to_string()
+    }
+}
+
+fn format_type(type_index: u16, type_registry: &TypeRegistry) -> String {
+    match type_registry.try_get(type_index) {
+        Some(_type) => _type.get_canonical_name().to_string(),
+        None => format!("#{}", type_index)
+    }
+}
+
+fn format_const_slot(index: u16, chunk: &Chunk) -> String {
+    match chunk.const_table.get(&index) {
+        Some(constant) => format_constant(constant),
+        None => "<missing constant>".to_string()
+    }
+}
+
+// Names the constant holding `class`, for the parents listed in a class header.
+fn format_class_slot(class: &Rc<Class>, chunk: &Chunk) -> String {
+    let found = chunk.const_table.iter().find(|(_, constant)| match constant {
+        Instance::Class(other) => Rc::ptr_eq(other, class),
+        _ => false
+    });
+    match found {
+        Some((slot, _)) => format!("k{}", slot),
+        None => "<missing class>".to_string()
+    }
+}
+
+// A parent of a class with the type arguments the class passes to it, in
+// which the class's type parameters are named `T1`, `T2` and so on.
+fn format_parent(class: &Rc<Class>, args: &[TypeArg], chunk: &Chunk) -> String {
+    let slot = format_class_slot(class, chunk);
+    if args.is_empty() {
+        return slot
+    }
+    let args: Vec<String> = args.iter().map(TypeArg::to_string).collect();
+    format!("{}<{}>", slot, args.join(","))
+}
+
+fn format_label(label: u16, chunk: &Chunk) -> String {
+    match chunk.jump_table.get(&label) {
+        Some(offset) => format!("-> {:04}", offset),
+        None => "<missing label>".to_string()
+    }
+}
+
+fn format_op(op: &OpCode, chunk: &Chunk, type_registry: &TypeRegistry) -> (String, Option<String>) {
+    match op {
+        OpCode::GetTrue => ("get_true".to_string(), None),
+        OpCode::GetFalse => ("get_false".to_string(), None),
+        OpCode::Get(true, index) => (format!("get_const k{}", index), Some(format_const_slot(*index, chunk))),
+        OpCode::Get(false, index) => (format!("get {}", index), None),
+        OpCode::Declare(true, type_index) => (format!("declare_const {}", format_type(*type_index, type_registry)), None),
+        OpCode::Declare(false, type_index) => (format!("declare {}", format_type(*type_index, type_registry)), None),
+        OpCode::Set(index) => (format!("set {}", index), None),
+        OpCode::Add => ("add".to_string(), None),
+        OpCode::Subtract => ("subtract".to_string(), None),
+        OpCode::Multiply => ("multiply".to_string(), None),
+        OpCode::Divide => ("divide".to_string(), None),
+        OpCode::Power => ("power".to_string(), None),
+        OpCode::Modulo => ("modulo".to_string(), None),
+        OpCode::DivideRounded(scale, mode) => (format!("divide_rounded {} {}", scale, mode.name()), None),
+        OpCode::BitAnd => ("bit_and".to_string(), None),
+        OpCode::BitOr => ("bit_or".to_string(), None),
+        OpCode::BitXor => ("bit_xor".to_string(), None),
+        OpCode::BitNot => ("bit_not".to_string(), None),
+        OpCode::ShiftLeft => ("shift_left".to_string(), None),
+        OpCode::ShiftRight => ("shift_right".to_string(), None),
+        OpCode::IntNegate => ("int_negate".to_string(), None),
+        OpCode::LogicNegate => ("logic_negate".to_string(), None),
+        OpCode::Less => ("less".to_string(), None),
+        OpCode::Greater => ("greater".to_string(), None),
+        OpCode::LessOrEq => ("less_or_eq".to_string(), None),
+        OpCode::GreaterOrEq => ("greater_or_eq".to_string(), None),
+        OpCode::Eq => ("eq".to_string(), None),
+        OpCode::NotEq => ("not_eq".to_string(), None),
+        OpCode::Is(type_index) => (format!("is {}", format_type(*type_index, type_registry)), None),
+        OpCode::Convert(type_index) => (format!("convert {}", format_type(*type_index, type_registry)), None),
+        OpCode::Concat => ("concat".to_string(), None),
+        OpCode::Format(index, count) => (format!("format k{} {}", index, count), Some(format_const_slot(*index, chunk))),
+        OpCode::Jump(false, label) => (format!("jump L{}", label), Some(format_label(*label, chunk))),
+        OpCode::Jump(true, label) => (format!("jump_if_false L{}", label), Some(format_label(*label, chunk))),
+        OpCode::JumpIfTrue(label) => (format!("jump_if_true L{}", label), Some(format_label(*label, chunk))),
+        OpCode::Switch(index) => (format!("switch S{}", index), Some(format_switch(*index, chunk))),
+        OpCode::Call => ("call".to_string(), None),
+        OpCode::Return(false) => ("return".to_string(), None),
+        OpCode::Return(true) => ("return_value".to_string(), None),
+        OpCode::InitArray(size, type_index) => (format!("init_array {} {}", size, format_type(*type_index, type_registry)), None),
+        OpCode::IndexGet => ("index_get".to_string(), None),
+        OpCode::IndexSet => ("index_set".to_string(), None),
+        OpCode::EnterScope(size) => (format!("enter_scope {}", size), None),
+        OpCode::ExitScope => ("exit_scope".to_string(), None),
+        OpCode::Print => ("print".to_string(), None),
+        OpCode::GetNative(index) => (format!("get_native k{}", index), Some(format_const_slot(*index, chunk))),
+        OpCode::New => ("new".to_string(), None),
+        OpCode::NewGeneric(type_index) => (format!("new_generic {}", format_type(*type_index, type_registry)), None),
+        OpCode::GetField(index) => (format!("get_field {}", index), None),
+        OpCode::SetField(index) => (format!("set_field {}", index), None),
+        OpCode::Invoke(index) => (format!("invoke k{}", index), Some(format_const_slot(*index, chunk))),
+        OpCode::Closure(index) => (format!("closure k{}", index), Some(format_const_slot(*index, chunk))),
+        OpCode::GetCaptured(index) => (format!("get_captured {}", index), None),
+        OpCode::SetCaptured(index) => (format!("set_captured {}", index), None),
+        OpCode::Throw => ("throw".to_string(), None),
+        OpCode::Try(index) => (format!("try H{}", index), chunk.handler_table.get(index).map(|handler| format_label(handler.label, chunk))),
+        OpCode::EndTry => ("end_try".to_string(), None),
+        OpCode::Yield => ("yield".to_string(), None),
+        OpCode::Resume => ("resume".to_string(), None),
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use crate::instance::Instance;
+use crate::bytecode::MAX_NESTING;
+
+// An error raised while executing bytecode, along with the offset of the
+// instruction that raised it.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub offset: usize
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, offset: usize) -> RuntimeError {
+        RuntimeError {
+            kind,
+            offset
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::InFunction(inner) => write!(f, "{}, called from instruction {:04}", inner, self.offset),
+            kind => write!(f, "{} (at instruction {:04})", kind, self.offset)
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[derive(Debug, Clone)]
+pub enum ErrorKind {
+    // A value of type `found` was used where `expected` was required.
+    TypeMismatch { expected: Rc<String>, found: Rc<String> },
+    // An operator was applied to operands it is not defined for.
+    UnsupportedOperands { operation: &'static str, left: Rc<String>, right: Rc<String> },
+    UnsupportedOperand { operation: &'static str, operand: Rc<String> },
+    NegativeExponent,
+    // An integer operation whose result did not fit in its type, under
+    // `OverflowPolicy::Error`.
+    IntegerOverflow { operation: &'static str, type_name: Rc<String> },
+    DivisionByZero,
+    InvalidShift { amount: i128, type_name: Rc<String> },
+    LossyConversion { value: Rc<String>, target: Rc<String> },
+    StackUnderflow,
+    NotIndexable(Rc<String>),
+    InvalidIndex(Rc<String>),
+    IndexOutOfBounds { index: i128, length: usize },
+    InvalidRange { start: i128, end: i128 },
+    InvalidFormat(Rc<String>),
+    UndefinedJump(u16),
+    UndefinedHandler(u16),
+    UndefinedSwitch(u16),
+    // An `EndTry` without a matching `Try`.
+    HandlerUnderflow,
+    // A value thrown by `Throw` that nothing caught.
+    Thrown(Instance),
+    UndefinedVariable(u16),
+    UndefinedCapture(u16),
+    YieldOutsideCoroutine,
+    CoroutineRunning,
+    CoroutineFinished,
+    ConstReassignment,
+    MissingConstant(u16),
+    UnknownType(Rc<String>),
+    UnknownTypeIndex(u16),
+    ArityMismatch { expected: usize, found: usize },
+    ScopeUnderflow,
+    // More variables were declared than the register has slots for.
+    RegisterOverflow,
+    UnknownNative(Rc<String>),
+    UndefinedField(u16),
+    UndefinedMethod { class: Rc<String>, method: Rc<String> },
+    DuplicateType(Rc<String>),
+    // More types were registered than there are type indices.
+    TypeRegistryOverflow,
+    NotGeneric(Rc<String>),
+    TypeArgumentMismatch { type_name: Rc<String>, expected: usize, found: usize },
+    UndefinedTypeParam(usize),
+    // A type name nests type arguments more than `bytecode::MAX_NESTING` levels deep.
+    TypeTooDeeplyNested,
+    // Raised by a `Call` whose callee failed; holds the callee's own error.
+    InFunction(Box<RuntimeError>),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::TypeMismatch { expected, found } => write!(f, "type mismatch: expected {} but found {}", expected, found),
+            ErrorKind::UnsupportedOperands { operation, left, right } => write!(f, "cannot {} {} and {}", operation, left, right),
+            ErrorKind::UnsupportedOperand { operation, operand } => write!(f, "cannot {} {}", operation, operand),
+            ErrorKind::NegativeExponent => write!(f, "integer exponent is negative"),
+            ErrorKind::IntegerOverflow { operation, type_name } => write!(f, "cannot {}: result overflows {}", operation, type_name),
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::InvalidShift { amount, type_name } => write!(f, "cannot shift {} by {} bit(s)", type_name, amount),
+            ErrorKind::LossyConversion { value, target } => write!(f, "cannot convert {} to {} without losing its value", value, target),
+            ErrorKind::StackUnderflow => write!(f, "the stack was empty"),
+            ErrorKind::NotIndexable(name) => write!(f, "{} is not indexable", name),
+            ErrorKind::InvalidIndex(name) => write!(f, "{} cannot be used as an index", name),
+            ErrorKind::IndexOutOfBounds { index, length } => write!(f, "index {} is out of bounds for length {}", index, length),
+            ErrorKind::InvalidRange { start, end } => write!(f, "range start {} is after its end {}", start, end),
+            ErrorKind::InvalidFormat(message) => write!(f, "invalid format: {}", message),
+            ErrorKind::UndefinedJump(label) => write!(f, "jump label {} does not exist", label),
+            ErrorKind::UndefinedHandler(index) => write!(f, "exception handler {} does not exist", index),
+            ErrorKind::UndefinedSwitch(index) => write!(f, "switch table {} does not exist", index),
+            ErrorKind::HandlerUnderflow => write!(f, "ended a try block that was not entered"),
+            ErrorKind::Thrown(value) => write!(f, "uncaught exception: {}", value),
+            ErrorKind::UndefinedVariable(index) => write!(f, "register slot {} is not declared", index),
+            ErrorKind::UndefinedCapture(index) => write!(f, "captured variable {} does not exist", index),
+            ErrorKind::YieldOutsideCoroutine => write!(f, "cannot yield outside of a coroutine"),
+            ErrorKind::CoroutineRunning => write!(f, "cannot resume a coroutine that is already running"),
+            ErrorKind::CoroutineFinished => write!(f, "cannot resume a coroutine that has finished"),
+            ErrorKind::ConstReassignment => write!(f, "attempted to set a constant variable"),
+            ErrorKind::MissingConstant(index) => write!(f, "constant table slot {} is empty", index),
+            ErrorKind::UnknownType(name) => write!(f, "type {} does not exist", name),
+            ErrorKind::UnknownTypeIndex(index) => write!(f, "type index {} does not exist", index),
+            ErrorKind::ArityMismatch { expected, found } => write!(f, "expected {} argument(s) but found {}", expected, found),
+            ErrorKind::ScopeUnderflow => write!(f, "exited an inner scope that did not exist"),
+            ErrorKind::RegisterOverflow => write!(f, "the register is out of slots for variables"),
+            ErrorKind::UnknownNative(name) => write!(f, "native function {} does not exist", name),
+            ErrorKind::UndefinedField(index) => write!(f, "field {} does not exist", index),
+            ErrorKind::UndefinedMethod { class, method } => write!(f, "{} has no method {}", class, method),
+            ErrorKind::DuplicateType(name) => write!(f, "type {} is already registered", name),
+            ErrorKind::TypeRegistryOverflow => write!(f, "the type registry is out of indices for types"),
+            ErrorKind::NotGeneric(name) => write!(f, "type {} is not generic", name),
+            ErrorKind::TypeArgumentMismatch { type_name, expected, found } => write!(f, "{} takes {} type argument(s) but {} were given", type_name, expected, found),
+            ErrorKind::UndefinedTypeParam(index) => write!(f, "type parameter T{} does not exist", index + 1),
+            ErrorKind::TypeTooDeeplyNested => write!(f, "type arguments are nested more than {} levels deep", MAX_NESTING),
+            ErrorKind::InFunction(inner) => write!(f, "{}", inner),
+        }
+    }
+}
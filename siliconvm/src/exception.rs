@@ -0,0 +1,75 @@
+// The classes errors raised by the VM are thrown as.
+//
+// Errors a program can recover from, such as a division by zero or an index
+// out of bounds, become instances of one of the classes below when a `Try` is
+// there to catch them, with the error's message in their `message` field.
+// Errors that mean the bytecode itself is broken, such as a stack underflow or
+// a missing constant, cannot be caught.
+//
+//   silicon.lang.Exception
+//     silicon.lang.ArithmeticException   overflows, division by zero, bad shifts and lossy conversions
+//     silicon.lang.IndexException        bad indices and ranges
+//     silicon.lang.TypeException         type mismatches and unsupported operands
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::error::ErrorKind;
+use crate::instance::{Class, Field, Type};
+use crate::runtime::TypeRegistry;
+use crate::string_pool::StringPool;
+
+pub(crate) struct Exceptions {
+    pub(crate) exception: Rc<Class>,
+    arithmetic: Rc<Class>,
+    index: Rc<Class>,
+    type_error: Rc<Class>
+}
+
+impl Exceptions {
+    pub(crate) fn new(type_registry: &mut TypeRegistry, string_pool: &mut StringPool) -> Exceptions {
+        let string_type = type_registry.get_by_name(string_pool.pool_str("silicon.lang.String")).expect("core types are registered");
+        let message = Field::new(string_pool.pool_str("message"), string_type, true);
+        let exception = define(type_registry, string_pool, "silicon.lang.Exception", None, vec![message]);
+        Exceptions {
+            arithmetic: define(type_registry, string_pool, "silicon.lang.ArithmeticException", Some(&exception), vec![]),
+            index: define(type_registry, string_pool, "silicon.lang.IndexException", Some(&exception), vec![]),
+            type_error: define(type_registry, string_pool, "silicon.lang.TypeException", Some(&exception), vec![]),
+            exception
+        }
+    }
+
+    // The class `kind` is thrown as, or None if it cannot be caught.
+    pub(crate) fn class_for(&self, kind: &ErrorKind) -> Option<&Rc<Class>> {
+        let class = match kind {
+            ErrorKind::NegativeExponent
+            | ErrorKind::IntegerOverflow { .. }
+            | ErrorKind::DivisionByZero
+            | ErrorKind::InvalidShift { .. }
+            | ErrorKind::LossyConversion { .. } => &self.arithmetic,
+            ErrorKind::NotIndexable(_)
+            | ErrorKind::InvalidIndex(_)
+            | ErrorKind::IndexOutOfBounds { .. }
+            | ErrorKind::InvalidRange { .. } => &self.index,
+            ErrorKind::TypeMismatch { .. }
+            | ErrorKind::UnsupportedOperands { .. }
+            | ErrorKind::UnsupportedOperand { .. }
+            | ErrorKind::ArityMismatch { .. } => &self.type_error,
+            ErrorKind::InvalidFormat(_)
+            | ErrorKind::ConstReassignment
+            | ErrorKind::UndefinedField(_)
+            | ErrorKind::UndefinedMethod { .. }
+            | ErrorKind::UnknownNative(_)
+            | ErrorKind::CoroutineRunning
+            | ErrorKind::CoroutineFinished => &self.exception,
+            _ => return None
+        };
+        Some(class)
+    }
+}
+
+fn define(type_registry: &mut TypeRegistry, string_pool: &mut StringPool, name: &str, superclass: Option<&Rc<Class>>, fields: Vec<Field>) -> Rc<Class> {
+    let supertype = superclass.map(|superclass| Rc::clone(&superclass._type));
+    let _type = Rc::new(Type::new_derived(string_pool.pool_str(name), 0, supertype, vec![]));
+    type_registry.register_type(Rc::clone(&_type)).expect("exception types are only registered once");
+    Rc::new(Class::new(_type, superclass.cloned(), vec![], fields, HashMap::new()))
+}
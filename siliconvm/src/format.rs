@@ -0,0 +1,239 @@
+// String formatting for Silicon VM
+//
+// `Format` fills a template with values taken from the stack. Placeholders are
+// written `{}` or `{INDEX}`, optionally followed by `:SPEC`, and `{{` and `}}`
+// stand for literal braces. As in Rust, `{}` takes the argument after the one
+// the previous `{}` took, whatever explicit indices come in between. SPEC is
+//
+//   [[FILL]ALIGN][0][WIDTH][.PRECISION][RADIX]
+//
+// where ALIGN is `<`, `^` or `>`, `0` pads numbers with zeros after their sign,
+// WIDTH is a minimum width in chars and RADIX is `b`, `o`, `x` or `X`, for
+// integers only. PRECISION is the number of digits after the point for floats,
+// decimals and complex numbers, and the maximum number of chars for strings.
+//
+// Values are written without the type suffixes and quotes their `Display`
+// gives them in listings: strings and chars as their text and numbers as plain
+// digits, with every other value as it is displayed. Numbers are aligned to
+// the right by default and everything else to the left.
+
+use std::fmt::{Binary, Display, LowerHex, Octal, UpperHex};
+use std::rc::Rc;
+use num_bigint::BigInt;
+use crate::decimal;
+use crate::error::ErrorKind;
+use crate::instance::Instance;
+
+pub(crate) fn format(template: &str, args: &[Instance]) -> Result<String, ErrorKind> {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    let mut next_implicit = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => { chars.next(); result.push('{') },
+            '}' if chars.peek() == Some(&'}') => { chars.next(); result.push('}') },
+            '}' => return Err(invalid("unmatched `}`".to_string())),
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(invalid(format!("unclosed placeholder `{{{}`", placeholder)))
+                    }
+                }
+                let (index, spec) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+                let index = match index {
+                    "" => { next_implicit += 1; next_implicit - 1 },
+                    index => index.parse().map_err(|_| invalid(format!("`{}` is not an argument index", index)))?
+                };
+                let arg = match args.get(index) {
+                    Some(arg) => arg,
+                    None => return Err(invalid(format!("there is no argument {} among {}", index, args.len())))
+                };
+                write_value(&mut result, arg, &Spec::parse(spec)?)?
+            },
+            c => result.push(c)
+        }
+    }
+    Ok(result)
+}
+
+fn invalid(message: String) -> ErrorKind {
+    ErrorKind::InvalidFormat(Rc::new(message))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Right
+}
+
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    radix: Option<char>
+}
+
+impl Spec {
+    fn parse(text: &str) -> Result<Spec, ErrorKind> {
+        let mut spec = Spec { fill: ' ', align: None, zero: false, width: 0, precision: None, radix: None };
+        let chars: Vec<char> = text.chars().collect();
+        let align = |c: char| match c {
+            '<' => Some(Align::Left),
+            '^' => Some(Align::Center),
+            '>' => Some(Align::Right),
+            _ => None
+        };
+
+        let mut pos = 0;
+        if let Some(found) = chars.get(1).and_then(|c| align(*c)) {
+            spec.fill = chars[0];
+            spec.align = Some(found);
+            pos = 2;
+        } else if let Some(found) = chars.first().and_then(|c| align(*c)) {
+            spec.align = Some(found);
+            pos = 1;
+        }
+        if chars.get(pos) == Some(&'0') {
+            spec.zero = true;
+            pos += 1;
+        }
+        let (width, end) = read_number(&chars, pos);
+        spec.width = width.unwrap_or(0);
+        pos = end;
+        if chars.get(pos) == Some(&'.') {
+            let (precision, end) = read_number(&chars, pos + 1);
+            if precision.is_none() {
+                return Err(invalid(format!("`{}` has no precision after its `.`", text)))
+            }
+            spec.precision = precision;
+            pos = end;
+        }
+        if let Some(radix) = chars.get(pos).filter(|c| matches!(c, 'b' | 'o' | 'x' | 'X')) {
+            spec.radix = Some(*radix);
+            pos += 1;
+        }
+        if pos != chars.len() {
+            return Err(invalid(format!("`{}` is not a valid format specifier", text)))
+        }
+        Ok(spec)
+    }
+}
+
+fn read_number(chars: &[char], start: usize) -> (Option<usize>, usize) {
+    let end = chars[start.min(chars.len())..].iter().position(|c| !c.is_ascii_digit()).map_or(chars.len(), |len| start + len);
+    let digits: String = chars[start.min(end)..end].iter().collect();
+    (digits.parse().ok(), end)
+}
+
+fn write_value(result: &mut String, value: &Instance, spec: &Spec) -> Result<(), ErrorKind> {
+    let unsupported = |what: &str| invalid(format!("{} cannot be formatted with a {}", value.get_canonical_name(), what));
+    if spec.radix.is_some() && !is_integer(value) {
+        return Err(unsupported("radix"))
+    }
+
+    let text = match value {
+        Instance::Str(string) => match spec.precision {
+            Some(precision) => string.chars().take(precision).collect(),
+            None => string.to_string()
+        },
+        Instance::Byte(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::UByte(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::Int16(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::UInt16(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::Int32(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::UInt32(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::Int64(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::UInt64(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::Int128(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::UInt128(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::BigInt(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::UBigInt(value) => integer(value, spec).ok_or_else(|| unsupported("precision"))?,
+        Instance::Float32(value) => with_precision(value, spec),
+        Instance::Float64(value) => with_precision(value, spec),
+        Instance::Complex(value) => with_precision(value, spec),
+        Instance::Char(c) => c.to_string(),
+        value => match decimal_parts(value) {
+            Some((unscaled, scale)) => match spec.precision {
+                Some(precision) => decimal::format_with_precision(&unscaled, scale, precision.min(u8::MAX as usize) as u8),
+                None => decimal::format_unscaled(&unscaled, scale)
+            },
+            None if spec.precision.is_some() => return Err(unsupported("precision")),
+            None => value.to_string()
+        }
+    };
+
+    let is_number = is_integer(value) || decimal_parts(value).is_some()
+        || matches!(value, Instance::Float32(_) | Instance::Float64(_) | Instance::Complex(_));
+    let padding = spec.width.saturating_sub(text.chars().count());
+    if padding == 0 {
+        result.push_str(&text);
+        return Ok(())
+    }
+    if spec.zero && is_number {
+        let sign_length = if text.starts_with('-') || text.starts_with('+') { 1 } else { 0 };
+        result.push_str(&text[..sign_length]);
+        result.extend(std::iter::repeat_n('0', padding));
+        result.push_str(&text[sign_length..]);
+        return Ok(())
+    }
+
+    let align = spec.align.unwrap_or(if is_number { Align::Right } else { Align::Left });
+    let before = match align {
+        Align::Left => 0,
+        Align::Center => padding / 2,
+        Align::Right => padding
+    };
+    result.extend(std::iter::repeat_n(spec.fill, before));
+    result.push_str(&text);
+    result.extend(std::iter::repeat_n(spec.fill, padding - before));
+    Ok(())
+}
+
+// Integers take a radix but no precision, so this is None when one is given.
+fn integer<T>(value: T, spec: &Spec) -> Option<String> where T: Display + Binary + Octal + LowerHex + UpperHex {
+    if spec.precision.is_some() {
+        return None
+    }
+    let text = match spec.radix {
+        Some('b') => format!("{:b}", value),
+        Some('o') => format!("{:o}", value),
+        Some('x') => format!("{:x}", value),
+        Some('X') => format!("{:X}", value),
+        _ => format!("{}", value)
+    };
+    Some(text)
+}
+
+fn with_precision<T>(value: T, spec: &Spec) -> String where T: Display {
+    match spec.precision {
+        Some(precision) => format!("{:.*}", precision, value),
+        None => format!("{}", value)
+    }
+}
+
+fn is_integer(value: &Instance) -> bool {
+    matches!(value, Instance::Byte(_) | Instance::UByte(_) | Instance::Int16(_) | Instance::UInt16(_) | Instance::Int32(_)
+        | Instance::UInt32(_) | Instance::Int64(_) | Instance::UInt64(_) | Instance::Int128(_) | Instance::UInt128(_)
+        | Instance::BigInt(_) | Instance::UBigInt(_))
+}
+
+fn decimal_parts(value: &Instance) -> Option<(BigInt, u8)> {
+    match value {
+        Instance::Decimal16(value) => Some(value.to_unscaled()),
+        Instance::UDecimal16(value) => Some(value.to_unscaled()),
+        Instance::Decimal32(value) => Some(value.to_unscaled()),
+        Instance::UDecimal32(value) => Some(value.to_unscaled()),
+        Instance::Decimal64(value) => Some(value.to_unscaled()),
+        Instance::UDecimal64(value) => Some(value.to_unscaled()),
+        Instance::Decimal128(value) => Some(value.to_unscaled()),
+        Instance::UDecimal128(value) => Some(value.to_unscaled()),
+        _ => None
+    }
+}
@@ -0,0 +1,182 @@
+// The heap of arrays, class instances, closures and coroutines.
+//
+// These are reference counted, which frees most of them as soon as they are
+// dropped but never frees cycles, such as an array stored in one of its own
+// elements or a closure that captures the variable holding it. The heap keeps
+// a weak reference to everything allocated through it so that `collect` can
+// trace what is still reachable from the roots it is given and empty whatever
+// is not, which breaks the cycles and lets reference counting free them. A
+// closure is emptied by dropping its captured variables, which are left as
+// they are, since they may still be shared with a live frame, and a
+// coroutine by finishing it, which drops whatever it held while suspended.
+//
+// Collection must only happen where every live value is reachable from the
+// roots, which for the VM is between instructions.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+use std::rc::{Rc, Weak};
+use crate::instance::{Instance, ClassInstance, Type, Function, Variable};
+use crate::coroutine::{Coroutine, CoroutineState};
+
+pub struct Heap {
+    objects: Vec<HeapObject>,
+    // The number of allocations since the last collection after which the
+    // next one happens.
+    threshold: usize,
+    allocated_since_collection: usize,
+    stats: GcStats
+}
+
+enum HeapObject {
+    Array(Weak<RefCell<Vec<Instance>>>),
+    Instance(Weak<ClassInstance>),
+    Closure(Weak<Function>),
+    Coroutine(Weak<Coroutine>)
+}
+
+// Totals since the heap was created, except for `live`, which is the number of
+// objects the heap was tracking at the end of the last collection.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GcStats {
+    pub collections: u64,
+    pub allocated: u64,
+    pub collected: u64,
+    pub live: usize
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} collections, {} allocated, {} collected, {} live", self.collections, self.allocated, self.collected, self.live)
+    }
+}
+
+const MIN_THRESHOLD: usize = 1024;
+
+impl Heap {
+    pub(crate) fn new() -> Heap {
+        Heap {
+            objects: vec![],
+            threshold: MIN_THRESHOLD,
+            allocated_since_collection: 0,
+            stats: Default::default()
+        }
+    }
+
+    pub(crate) fn alloc_array(&mut self, elements: Vec<Instance>, _type: Rc<Type>) -> Instance {
+        let array = Rc::new(RefCell::new(elements));
+        self.track(HeapObject::Array(Rc::downgrade(&array)));
+        Instance::Array(array, _type)
+    }
+
+    pub(crate) fn alloc_instance(&mut self, instance: ClassInstance) -> Instance {
+        let instance = Rc::new(instance);
+        self.track(HeapObject::Instance(Rc::downgrade(&instance)));
+        Instance::ClassInstance(instance)
+    }
+
+    pub(crate) fn alloc_closure(&mut self, closure: Function) -> Instance {
+        let closure = Rc::new(closure);
+        self.track(HeapObject::Closure(Rc::downgrade(&closure)));
+        Instance::Func(closure)
+    }
+
+    pub(crate) fn alloc_coroutine(&mut self, coroutine: Coroutine) -> Instance {
+        let coroutine = Rc::new(coroutine);
+        self.track(HeapObject::Coroutine(Rc::downgrade(&coroutine)));
+        Instance::Coroutine(coroutine)
+    }
+
+    fn track(&mut self, object: HeapObject) {
+        self.objects.push(object);
+        self.allocated_since_collection += 1;
+        self.stats.allocated += 1;
+    }
+
+    // Whether enough has been allocated since the last collection to run another.
+    pub(crate) fn should_collect(&self) -> bool {
+        self.allocated_since_collection >= self.threshold
+    }
+
+    // Empties every object not reachable from `roots` and returns how many there were.
+    pub(crate) fn collect<'a>(&mut self, roots: impl Iterator<Item = &'a Instance>) -> usize {
+        let marked = mark(roots);
+
+        // Emptied contents are only dropped once every object has been looked
+        // at, since dropping them may free objects further down the list.
+        let mut garbage: Vec<Vec<Instance>> = vec![];
+        let mut upvalues: Vec<Vec<Rc<RefCell<Variable>>>> = vec![];
+        let mut finished: Vec<CoroutineState> = vec![];
+        self.objects.retain(|object| match object {
+            HeapObject::Array(array) => match array.upgrade() {
+                Some(array) if !marked.contains(&address(&array)) => { garbage.push(mem::take(&mut *array.borrow_mut())); false },
+                Some(_) => true,
+                None => false
+            },
+            HeapObject::Instance(instance) => match instance.upgrade() {
+                Some(instance) if !marked.contains(&address(&instance)) => { garbage.push(mem::take(&mut *instance.fields.borrow_mut())); false },
+                Some(_) => true,
+                None => false
+            },
+            HeapObject::Closure(closure) => match closure.upgrade() {
+                Some(closure) if !marked.contains(&address(&closure)) => { upvalues.push(mem::take(&mut *closure.upvalues.borrow_mut())); false },
+                Some(_) => true,
+                None => false
+            },
+            HeapObject::Coroutine(coroutine) => match coroutine.upgrade() {
+                Some(coroutine) if !marked.contains(&address(&coroutine)) => { finished.push(coroutine.state.replace(CoroutineState::Done)); false },
+                Some(_) => true,
+                None => false
+            }
+        });
+        let collected = garbage.len() + upvalues.len() + finished.len();
+        drop(garbage);
+        drop(upvalues);
+        drop(finished);
+
+        self.stats.collections += 1;
+        self.stats.collected += collected as u64;
+        self.stats.live = self.objects.len();
+        self.threshold = (self.objects.len() * 2).max(MIN_THRESHOLD);
+        self.allocated_since_collection = 0;
+        collected
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+}
+
+// The addresses of every array, instance, closure and coroutine reachable from `roots`.
+fn mark<'a>(roots: impl Iterator<Item = &'a Instance>) -> HashSet<usize> {
+    let mut marked = HashSet::new();
+    let mut pending: Vec<Instance> = roots.filter(|root| is_traced(root)).cloned().collect();
+    while let Some(instance) = pending.pop() {
+        match &instance {
+            Instance::Array(array, _) if marked.insert(address(array)) => {
+                pending.extend(array.borrow().iter().filter(|element| is_traced(element)).cloned())
+            },
+            Instance::ClassInstance(object) if marked.insert(address(object)) => {
+                pending.extend(object.fields.borrow().iter().filter(|field| is_traced(field)).cloned())
+            },
+            Instance::Func(closure) if marked.insert(address(closure)) => {
+                pending.extend(closure.upvalues.borrow().iter().map(|variable| variable.borrow().stored.to_owned()).filter(is_traced))
+            },
+            Instance::Coroutine(coroutine) if marked.insert(address(coroutine)) => {
+                pending.extend(coroutine.held_values().into_iter().filter(is_traced))
+            },
+            _ => {}
+        }
+    }
+    marked
+}
+
+fn is_traced(instance: &Instance) -> bool {
+    matches!(instance, Instance::Array(_, _) | Instance::ClassInstance(_) | Instance::Func(_) | Instance::Coroutine(_))
+}
+
+fn address<T>(object: &Rc<T>) -> usize {
+    Rc::as_ptr(object) as *const () as usize
+}
@@ -1,8 +1,13 @@
 use std::rc::Rc;
+use std::collections::HashMap;
 use crate::opcode::Chunk;
 use std::cell::RefCell;
 use std::fmt::{Display, Formatter, Error};
 use std::fmt;
+use crate::error::ErrorKind;
+use crate::runtime::VM;
+use crate::decimal::Decimal;
+use crate::coroutine::Coroutine;
 
 // Represents instances created at runtime
 #[derive(Clone, Debug)]
@@ -18,39 +23,50 @@ pub enum Instance {
     UInt64(u64),
     Int128(i128),
     UInt128(u128),
-    //Fixed-point precision.
-    //Decimal16(),
-    //UDecimal16(),
-    //Decimal32(),
-    //UDecimal32(),
-    //Decimal64(),
-    //UDecimal64(),
-    //Decimal128(),
-    //UDecimal128(),
+    //Fixed-point precision, backed by the integer type of the same width.
+    Decimal16(Decimal<i16>),
+    UDecimal16(Decimal<u16>),
+    Decimal32(Decimal<i32>),
+    UDecimal32(Decimal<u32>),
+    Decimal64(Decimal<i64>),
+    UDecimal64(Decimal<u64>),
+    Decimal128(Decimal<i128>),
+    UDecimal128(Decimal<u128>),
     Float32(f32),
     Float64(f64),
+    //Arbitrary-precision integers.
+    BigInt(num_bigint::BigInt),
+    UBigInt(num_bigint::BigUint),
     //These are commented out for now but I would like to bring in the 'num' crate at some point
     //to introduce these types or make my own.
-    //BigInt(),
-    //UBigInt(),
     //BigFloat(),
     //BigDecimal(),
-    //Complex(),
+    //A complex number with Float64 parts.
+    Complex(num_complex::Complex64),
     Char(char),
     Str(Rc<String>),
+    //Represents an array, along with its `silicon.lang.Array<T>` type.
     Array(Rc<RefCell<Vec<Instance>>>, Rc<Type>),
     //Represents a custom class instance.
-    //ClassInstance(Box<ClassInstance>),
+    ClassInstance(Rc<ClassInstance>),
     //Represents a class object.
-    //Class(Box<Class>)
+    Class(Rc<Class>),
     //Represents a function.
-    // Func(Rc<Function>),
+    Func(Rc<Function>),
+    //A function that can be suspended and resumed; see `coroutine`.
+    Coroutine(Rc<Coroutine>),
     Void
 }
 
 impl Instance {
 
     pub fn get_canonical_name(&self) -> Rc<String> {
+        match self {
+            Instance::ClassInstance(instance) => return instance._type.get_canonical_name(),
+            Instance::Array(_, _type) => return _type.get_canonical_name(),
+            _ => {}
+        }
+
         Rc::new(
             match self {
                 Instance::Bool(_) => "silicon.lang.Boolean",
@@ -64,11 +80,24 @@ impl Instance {
                 Instance::UInt64(_) => "silicon.lang.UInt64",
                 Instance::Int128(_) => "silicon.lang.Int128",
                 Instance::UInt128(_) => "silicon.lang.UInt128",
+                Instance::Decimal16(_) => "silicon.lang.Decimal16",
+                Instance::UDecimal16(_) => "silicon.lang.UDecimal16",
+                Instance::Decimal32(_) => "silicon.lang.Decimal32",
+                Instance::UDecimal32(_) => "silicon.lang.UDecimal32",
+                Instance::Decimal64(_) => "silicon.lang.Decimal64",
+                Instance::UDecimal64(_) => "silicon.lang.UDecimal64",
+                Instance::Decimal128(_) => "silicon.lang.Decimal128",
+                Instance::UDecimal128(_) => "silicon.lang.UDecimal128",
                 Instance::Float32(_) => "silicon.lang.Float32",
                 Instance::Float64(_) => "silicon.lang.Float64",
+                Instance::Complex(_) => "silicon.lang.Complex",
+                Instance::BigInt(_) => "silicon.lang.BigInt",
+                Instance::UBigInt(_) => "silicon.lang.UBigInt",
                 Instance::Char(_) => "silicon.lang.Char",
                 Instance::Str(_) => "silicon.lang.String",
-                Instance::Array(_, _) => "silicon.lang.Array",
+                Instance::Func(_) => "silicon.lang.Func",
+                Instance::Class(_) => "silicon.lang.Class",
+                Instance::Coroutine(_) => "silicon.lang.Coroutine",
 
                 Instance::Void => "silicon.lang.Void",
                 _ => ""
@@ -91,100 +120,507 @@ impl Display for Instance {
             Instance::UInt64(uint64) => write!(f, "{}u64", uint64),
             Instance::Int128(int128) => write!(f, "{}i128", int128),
             Instance::UInt128(uint128) => write!(f, "{}u128", uint128),
+            Instance::Decimal16(decimal) => write!(f, "{}d16", decimal),
+            Instance::UDecimal16(decimal) => write!(f, "{}ud16", decimal),
+            Instance::Decimal32(decimal) => write!(f, "{}d32", decimal),
+            Instance::UDecimal32(decimal) => write!(f, "{}ud32", decimal),
+            Instance::Decimal64(decimal) => write!(f, "{}d64", decimal),
+            Instance::UDecimal64(decimal) => write!(f, "{}ud64", decimal),
+            Instance::Decimal128(decimal) => write!(f, "{}d128", decimal),
+            Instance::UDecimal128(decimal) => write!(f, "{}ud128", decimal),
             Instance::Float32(float32) => write!(f, "{}f32", float32),
             Instance::Float64(float64) => write!(f, "{}f64", float64),
+            Instance::Complex(complex) => write!(f, "{}", complex),
+            Instance::BigInt(bigint) => write!(f, "{}bi", bigint),
+            Instance::UBigInt(ubigint) => write!(f, "{}ubi", ubigint),
             Instance::Char(character) => write!(f, "'{}'", character),
             Instance::Str(string) => write!(f, "\"{}\"", string),
             Instance::Array(array, _type) => {
-                let mut array_string = format!("{}[", _type.get_canonical_name());
-                let borrowed = array.borrow_mut();
-
-                if !borrowed.is_empty() {
-                    for i in 0..borrowed.len() {
-                        match borrowed.get(i) {
-                            Some(instance) => {
-                                let item_string = format!("{}", instance);
-                                array_string.push_str(item_string.as_str());
-                                if i != borrowed.len() - 1 {
-                                    array_string.push_str(", ")
-                                }
-                            }
-                            None => panic!("Could not format array!")
-                        }
+                let element_name = match _type.get_type_args().first() {
+                    Some(element_type) => element_type.get_canonical_name(),
+                    None => _type.get_canonical_name()
+                };
+                let mut array_string = format!("{}[", element_name);
+                let borrowed = array.borrow();
 
+                for (i, instance) in borrowed.iter().enumerate() {
+                    let item_string = format!("{}", instance);
+                    array_string.push_str(item_string.as_str());
+                    if i != borrowed.len() - 1 {
+                        array_string.push_str(", ")
                     }
                 }
 
                 write!(f, "{}]", array_string)
             },
+            Instance::Func(function) => write!(f, "{}", function),
+            Instance::ClassInstance(instance) => {
+                let fields = instance.fields.borrow();
+                let mut instance_string = format!("{}{{", instance._type.get_canonical_name());
+                for (i, value) in fields.iter().enumerate() {
+                    if let Some(field) = instance.class.get_field(i as u16) {
+                        instance_string.push_str(&format!("{}: {}", field.name, value));
+                    }
+                    if i != fields.len() - 1 {
+                        instance_string.push_str(", ")
+                    }
+                }
+                write!(f, "{}}}", instance_string)
+            },
+            Instance::Class(class) => write!(f, "class {}", class._type.get_canonical_name()),
+            Instance::Coroutine(coroutine) => write!(f, "coroutine ({})", coroutine.status()),
 
             Instance::Void => write!(f, "{}", "void")
         };
     }
 }
 
+// A type known to the VM. Generic types have type parameters and are reified
+// into parameterised types such as `silicon.lang.Array<silicon.lang.Int32>`,
+// which `TypeRegistry::reify` interns so each is only created once.
 #[derive(Debug)]
 pub struct Type {
     pub(crate) canonical_name: Rc<String>,
-    is_generic: bool,
-    type_args: Vec<Rc<Type>>
+    type_param_count: usize,
+    type_args: Vec<Rc<Type>>,
+    pub(crate) supertype: Option<Rc<Type>>,
+    pub(crate) interfaces: Vec<Rc<Type>>,
+    // The type arguments a generic type passes to its supertype and to each of
+    // its interfaces, for the parents that depend on its type parameters and
+    // so can only be reified along with it. Empty for every other parent.
+    supertype_args: Vec<TypeArg>,
+    interface_args: Vec<Vec<TypeArg>>
+}
+
+// A type argument that a generic type passes to one of its parents, in terms
+// of its own type parameters. `Foo<K,V> extends Bar<V,Array<K>>` passes
+// `Param(1)` and `Applied(Array, [Param(0)])` to `Bar`.
+#[derive(Debug, Clone)]
+pub enum TypeArg {
+    // The type parameter at this position.
+    Param(usize),
+    Concrete(Rc<Type>),
+    // A generic type reified with arguments that depend on type parameters.
+    Applied(Rc<Type>, Vec<TypeArg>)
+}
+
+impl TypeArg {
+    pub fn uses_params(&self) -> bool {
+        match self {
+            TypeArg::Param(_) => true,
+            TypeArg::Concrete(_) => false,
+            TypeArg::Applied(_, args) => args.iter().any(TypeArg::uses_params)
+        }
+    }
+
+    // Checks that the argument only refers to the first `type_param_count`
+    // type parameters and gives every generic type the arguments it takes.
+    pub(crate) fn check(&self, type_param_count: usize) -> Result<(), ErrorKind> {
+        match self {
+            TypeArg::Param(index) if *index >= type_param_count => Err(ErrorKind::UndefinedTypeParam(*index)),
+            TypeArg::Param(_) | TypeArg::Concrete(_) => Ok(()),
+            TypeArg::Applied(generic, args) => {
+                generic.check_type_args(args.len())?;
+                args.iter().try_for_each(|arg| arg.check(type_param_count))
+            }
+        }
+    }
+}
+
+impl Display for TypeArg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeArg::Param(index) => write!(f, "T{}", index + 1),
+            TypeArg::Concrete(_type) => write!(f, "{}", _type.get_canonical_name()),
+            TypeArg::Applied(generic, args) => {
+                let args: Vec<String> = args.iter().map(TypeArg::to_string).collect();
+                write!(f, "{}<{}>", generic.canonical_name, args.join(","))
+            }
+        }
+    }
 }
 
 impl Type {
     pub fn new(canonical_name: Rc<String>) -> Type {
+        Type::new_derived(canonical_name, 0, None, vec![])
+    }
+
+    pub fn new_generic(canonical_name: Rc<String>, type_param_count: usize) -> Type {
+        Type::new_derived(canonical_name, type_param_count, None, vec![])
+    }
+
+    // A type that extends `supertype`, if any, and implements `interfaces`.
+    pub fn new_derived(canonical_name: Rc<String>, type_param_count: usize, supertype: Option<Rc<Type>>, interfaces: Vec<Rc<Type>>) -> Type {
+        let interface_args = vec![vec![]; interfaces.len()];
         Type {
             canonical_name,
-            is_generic: false,
-            type_args: vec![]
+            type_param_count,
+            type_args: vec![],
+            supertype,
+            interfaces,
+            supertype_args: vec![],
+            interface_args
         }
     }
 
-    pub fn new_generic(canonical_name: Rc<String>) -> Type {
+    // A type whose parents are each given with the type arguments to reify
+    // them with along with this type. Use `TypeRegistry::define_type` instead,
+    // which checks the arguments and reifies the parents that do not depend on
+    // this type's parameters straight away.
+    pub(crate) fn new_with_parent_args(canonical_name: Rc<String>, type_param_count: usize, supertype: Option<(Rc<Type>, Vec<TypeArg>)>, interfaces: Vec<(Rc<Type>, Vec<TypeArg>)>) -> Type {
+        let (supertype, supertype_args) = match supertype {
+            Some((supertype, args)) => (Some(supertype), args),
+            None => (None, vec![])
+        };
+        let (interfaces, interface_args) = interfaces.into_iter().unzip();
         Type {
             canonical_name,
-            is_generic: true,
-            type_args: vec![]
+            type_param_count,
+            type_args: vec![],
+            supertype,
+            interfaces,
+            supertype_args,
+            interface_args
         }
     }
 
     pub fn get_canonical_name(&self) -> Rc<String> {
-        let mut actual_name = format!("{}", self.canonical_name);
-        if self.type_args.len() > 0 {
-            actual_name.push_str("<");
-            let mut i: usize = 0;
-            for _type in &self.type_args {
-                let type_name = format!("{}", _type.get_canonical_name());
-                actual_name.push_str(type_name.as_str());
-                if i < *&self.type_args.len() {
-                    actual_name.push(',')
-                }
-            }
-            actual_name.push_str(">");
+        parameterised_name(&self.canonical_name, &self.type_args)
+    }
+
+    // Whether this is a generic type that has not been reified yet.
+    pub fn is_generic(&self) -> bool {
+        self.type_param_count > 0 && self.type_args.is_empty()
+    }
+
+    pub fn get_type_param_count(&self) -> usize {
+        self.type_param_count
+    }
+
+    pub fn get_type_args(&self) -> &[Rc<Type>] {
+        &self.type_args
+    }
+
+    // The type arguments this type passes to its supertype, including those
+    // of a supertype that was reified when this type was defined.
+    pub fn get_supertype_args(&self) -> Vec<TypeArg> {
+        match &self.supertype {
+            Some(supertype) => declared_args(supertype, &self.supertype_args),
+            None => vec![]
         }
+    }
 
-        Rc::new(actual_name)
+    // The same for the interface at `index`.
+    pub fn get_interface_args(&self, index: usize) -> Vec<TypeArg> {
+        declared_args(&self.interfaces[index], &self.interface_args[index])
     }
 
-    pub fn reify(&self, type_args: Vec<Rc<Type>>) {
-        if self.is_generic {
-            if type_args.len() == self.type_args.len() {
-                Type {
-                    canonical_name: Rc::clone(&self.canonical_name),
-                    is_generic: false,
-                    type_args
-                };
-            }
-            panic!("Error during reification process.")
+    pub(crate) fn check_type_args(&self, count: usize) -> Result<(), ErrorKind> {
+        if !self.is_generic() {
+            return Err(ErrorKind::NotGeneric(self.get_canonical_name()))
+        }
+        if count != self.type_param_count {
+            return Err(ErrorKind::TypeArgumentMismatch { type_name: self.get_canonical_name(), expected: self.type_param_count, found: count })
         }
-        panic!("Attempted to reify non-generic type.")
+        Ok(())
+    }
+
+    // Parameterises this generic type with `type_args`. `reify_parent` is given
+    // each parent that depends on the type parameters, with the arguments this
+    // type passes to it, and reifies it; other parents are kept as they are.
+    // Use `TypeRegistry::reify` instead, which interns the result and its parents.
+    pub(crate) fn reify<F>(&self, type_args: Vec<Rc<Type>>, mut reify_parent: F) -> Result<Type, ErrorKind>
+        where F: FnMut(&Rc<Type>, &[TypeArg], &[Rc<Type>]) -> Result<Rc<Type>, ErrorKind>
+    {
+        self.check_type_args(type_args.len())?;
+
+        let mut reify_parent = |parent: &Rc<Type>, args: &[TypeArg]| match args.is_empty() {
+            true => Ok(Rc::clone(parent)),
+            false => reify_parent(parent, args, &type_args)
+        };
+        let supertype = match &self.supertype {
+            Some(supertype) => Some(reify_parent(supertype, &self.supertype_args)?),
+            None => None
+        };
+        let interfaces = self.interfaces.iter().zip(self.interface_args.iter())
+            .map(|(interface, args)| reify_parent(interface, args))
+            .collect::<Result<Vec<Rc<Type>>, ErrorKind>>()?;
+
+        let interface_args = vec![vec![]; interfaces.len()];
+        Ok(Type {
+            canonical_name: Rc::clone(&self.canonical_name),
+            type_param_count: self.type_param_count,
+            type_args,
+            supertype,
+            interfaces,
+            supertype_args: vec![],
+            interface_args
+        })
+    }
+}
+
+// The name of `name` reified with `type_args`, such as
+// `silicon.lang.Array<silicon.lang.Int32>`.
+pub(crate) fn parameterised_name(name: &Rc<String>, type_args: &[Rc<Type>]) -> Rc<String> {
+    if type_args.is_empty() {
+        return Rc::clone(name)
+    }
+    let type_args: Vec<String> = type_args.iter().map(|_type| _type.get_canonical_name().to_string()).collect();
+    Rc::new(format!("{}<{}>", name, type_args.join(",")))
+}
 
+fn declared_args(parent: &Rc<Type>, args: &[TypeArg]) -> Vec<TypeArg> {
+    match args.is_empty() {
+        true => parent.type_args.iter().map(|_type| TypeArg::Concrete(Rc::clone(_type))).collect(),
+        false => args.to_vec()
     }
 }
 
 impl Type {
 
     pub fn is(&self, instance: &Instance) -> bool {
-        &*self.canonical_name == &*"silicon.lang.Object".to_string() || &*self.canonical_name == &*instance.get_canonical_name()
+        match instance {
+            Instance::ClassInstance(instance) => instance._type.is_subtype_of(self),
+            Instance::Array(_, _type) => _type.is_subtype_of(self),
+            _ => self.is_object() || (self.type_args.is_empty() && *self.canonical_name == *instance.get_canonical_name())
+        }
+    }
+
+    // Whether values of this type can be used where `other` is expected, which
+    // holds for the type itself, `silicon.lang.Object` and anything this type
+    // extends or implements, directly or not. A generic type that has not been
+    // reified accepts any of its parameterisations.
+    pub fn is_subtype_of(&self, other: &Type) -> bool {
+        if other.is_object() || self.is_same(other) || (other.is_generic() && self.canonical_name == other.canonical_name) {
+            return true
+        }
+        self.supertype.iter().chain(self.interfaces.iter()).any(|parent| parent.is_subtype_of(other))
+    }
+
+    fn is_same(&self, other: &Type) -> bool {
+        self.canonical_name == other.canonical_name
+            && self.type_args.len() == other.type_args.len()
+            && self.type_args.iter().zip(other.type_args.iter()).all(|(left, right)| left.is_same(right))
+    }
+
+    fn is_object(&self) -> bool {
+        *self.canonical_name == "silicon.lang.Object"
+    }
+}
+
+// A host function callable from bytecode. It receives its arguments already
+// checked against the declared parameter types.
+pub type NativeFunction = Rc<dyn Fn(&mut VM, Vec<Instance>) -> Result<Instance, ErrorKind>>;
+
+#[derive(Clone)]
+pub enum FunctionBody {
+    Bytecode(Rc<Chunk>),
+    Native(NativeFunction)
+}
+
+impl fmt::Debug for FunctionBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionBody::Bytecode(chunk) => write!(f, "Bytecode({:?})", chunk),
+            FunctionBody::Native(_) => write!(f, "Native")
+        }
+    }
+}
+
+// A function along with its signature. A function that captures variables is
+// only a template for closures, which `Closure` creates with a variable for
+// each of `captures` in `upvalues`, shared with wherever it was captured from.
+#[derive(Debug)]
+pub struct Function {
+    pub(crate) body: FunctionBody,
+    pub(crate) param_types: Vec<Rc<Type>>,
+    pub(crate) return_type: Rc<Type>,
+    pub(crate) captures: Vec<Capture>,
+    pub(crate) upvalues: RefCell<Vec<Rc<RefCell<Variable>>>>
+}
+
+// Where a closure gets a variable from when it is created: a register slot of
+// the frame creating it, or a variable that frame's own closure captured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Local(u16),
+    Captured(u16)
+}
+
+impl Function {
+    pub fn new(chunk: Rc<Chunk>, param_types: Vec<Rc<Type>>, return_type: Rc<Type>) -> Function {
+        Function {
+            body: FunctionBody::Bytecode(chunk),
+            param_types,
+            return_type,
+            captures: vec![],
+            upvalues: RefCell::new(vec![])
+        }
+    }
+
+    pub fn new_native(function: NativeFunction, param_types: Vec<Rc<Type>>, return_type: Rc<Type>) -> Function {
+        Function {
+            body: FunctionBody::Native(function),
+            param_types,
+            return_type,
+            captures: vec![],
+            upvalues: RefCell::new(vec![])
+        }
+    }
+
+    // A closure of this function over `upvalues`, one for each of its captures.
+    pub fn close_over(&self, upvalues: Vec<Rc<RefCell<Variable>>>) -> Function {
+        Function {
+            body: self.body.clone(),
+            param_types: self.param_types.clone(),
+            return_type: Rc::clone(&self.return_type),
+            captures: self.captures.clone(),
+            upvalues: RefCell::new(upvalues)
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self.param_types.iter().map(|_type| _type.get_canonical_name().to_string()).collect();
+        if let FunctionBody::Native(_) = self.body {
+            write!(f, "native ")?;
+        }
+        write!(f, "func({}) -> {}", params.join(", "), self.return_type.get_canonical_name())
+    }
+}
+
+#[derive(Debug)]
+pub struct Field {
+    pub(crate) name: Rc<String>,
+    pub(crate) _type: Rc<Type>,
+    pub(crate) is_const: bool
+}
+
+impl Field {
+    pub fn new(name: Rc<String>, _type: Rc<Type>, is_const: bool) -> Field {
+        Field {
+            name,
+            _type,
+            is_const
+        }
+    }
+}
+
+// A class object, describing the field layout and methods of its instances.
+//
+// `fields` only holds the fields the class declares itself; an instance's
+// layout starts with those of its superclass. Interfaces cannot be
+// instantiated and only contribute methods, which serve as defaults for the
+// classes implementing them.
+#[derive(Debug)]
+pub struct Class {
+    pub(crate) _type: Rc<Type>,
+    pub(crate) is_interface: bool,
+    pub(crate) superclass: Option<Rc<Class>>,
+    pub(crate) interfaces: Vec<Rc<Class>>,
+    pub(crate) fields: Vec<Field>,
+    pub(crate) methods: HashMap<Rc<String>, Rc<Function>>
+}
+
+impl Class {
+    // `_type` should have been created with the types of `superclass` and
+    // `interfaces` as its supertype and interfaces.
+    pub fn new(_type: Rc<Type>, superclass: Option<Rc<Class>>, interfaces: Vec<Rc<Class>>, fields: Vec<Field>, methods: HashMap<Rc<String>, Rc<Function>>) -> Class {
+        Class {
+            _type,
+            is_interface: false,
+            superclass,
+            interfaces,
+            fields,
+            methods
+        }
+    }
+
+    pub fn new_interface(_type: Rc<Type>, interfaces: Vec<Rc<Class>>, methods: HashMap<Rc<String>, Rc<Function>>) -> Class {
+        Class {
+            _type,
+            is_interface: true,
+            superclass: None,
+            interfaces,
+            fields: vec![],
+            methods
+        }
+    }
+
+    // Looks in this class, then its superclasses, then the interfaces along the
+    // way, so that overrides win over inherited and default methods.
+    pub fn get_method(&self, name: &Rc<String>) -> Option<Rc<Function>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method))
+        }
+        if let Some(method) = self.superclass.as_ref().and_then(|superclass| superclass.get_method(name)) {
+            return Some(method)
+        }
+        self.interfaces.iter().find_map(|interface| interface.get_method(name))
+    }
+
+    // The number of fields in an instance, including inherited ones.
+    pub fn field_count(&self) -> usize {
+        self.inherited_field_count() + self.fields.len()
+    }
+
+    pub fn get_field(&self, index: u16) -> Option<&Field> {
+        let inherited = self.inherited_field_count();
+        match &self.superclass {
+            Some(superclass) if (index as usize) < inherited => superclass.get_field(index),
+            _ => self.fields.get(index as usize - inherited)
+        }
+    }
+
+    fn inherited_field_count(&self) -> usize {
+        self.superclass.as_ref().map_or(0, |superclass| superclass.field_count())
+    }
+}
+
+// An instance of a class. Fields start out as `void` until they are first set.
+// `_type` is the class's own type, or a reification of it for generic classes.
+#[derive(Debug)]
+pub struct ClassInstance {
+    pub(crate) class: Rc<Class>,
+    pub(crate) _type: Rc<Type>,
+    pub(crate) fields: RefCell<Vec<Instance>>
+}
+
+impl ClassInstance {
+    pub fn new(class: Rc<Class>, _type: Rc<Type>) -> ClassInstance {
+        let fields = vec![Instance::Void; class.field_count()];
+        ClassInstance {
+            class,
+            _type,
+            fields: RefCell::new(fields)
+        }
+    }
+
+    pub fn get_field(&self, index: u16) -> Result<Instance, ErrorKind> {
+        match self.fields.borrow().get(index as usize) {
+            Some(value) => Ok(value.to_owned()),
+            None => Err(ErrorKind::UndefinedField(index))
+        }
+    }
+
+    pub fn set_field(&self, index: u16, value: Instance) -> Result<(), ErrorKind> {
+        let field = match self.class.get_field(index) {
+            Some(field) => field,
+            None => return Err(ErrorKind::UndefinedField(index))
+        };
+        // The fields of an instance the heap has broken out of a cycle are
+        // gone, even though its class still has them.
+        let mut fields = self.fields.borrow_mut();
+        let stored = fields.get_mut(index as usize).ok_or(ErrorKind::UndefinedField(index))?;
+        if field.is_const && !matches!(stored, Instance::Void) {
+            return Err(ErrorKind::ConstReassignment)
+        }
+        if !field._type.is(&value) {
+            return Err(ErrorKind::TypeMismatch { expected: field._type.get_canonical_name(), found: value.get_canonical_name() })
+        }
+        *stored = value;
+        Ok(())
     }
 }
 
@@ -205,15 +641,36 @@ impl Variable {
         }
     }
 
-    pub(crate) fn set(&mut self, instance: Instance) {
+    pub(crate) fn set(&mut self, instance: Instance) -> Result<(), ErrorKind> {
         if self.is_const {
-            panic!("Attempted to set constant variable!")
+            return Err(ErrorKind::ConstReassignment)
         }
 
         if self._type.is(&instance) {
             self.stored = instance;
-            return;
+            return Ok(());
         }
-        panic!("Type mismatch!")
+        Err(ErrorKind::TypeMismatch { expected: self._type.get_canonical_name(), found: instance.get_canonical_name() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emptied_instances_have_no_fields() {
+        let object = Rc::new(Type::new(Rc::new("silicon.lang.Object".to_string())));
+        let node = Rc::new(Type::new(Rc::new("silicon.test.Node".to_string())));
+        let fields = vec![Field::new(Rc::new("next".to_string()), object, false)];
+        let class = Rc::new(Class::new(Rc::clone(&node), None, vec![], fields, HashMap::new()));
+        let instance = ClassInstance::new(class, node);
+        assert!(instance.set_field(0, Instance::Bool(true)).is_ok());
+        assert!(matches!(instance.set_field(1, Instance::Bool(true)), Err(ErrorKind::UndefinedField(1))));
+
+        // As the heap leaves an instance it broke out of a cycle.
+        instance.fields.borrow_mut().clear();
+        assert!(matches!(instance.set_field(0, Instance::Bool(true)), Err(ErrorKind::UndefinedField(0))));
+        assert!(matches!(instance.get_field(0), Err(ErrorKind::UndefinedField(0))));
     }
-}
\ No newline at end of file
+}
@@ -1,17 +1,11 @@
 use std::env;
+use std::fs;
 use std::process;
 
-use silicon;
-use silicon::Config;
-use std::path::PathBuf;
 use crate::runtime::{VM, CallFrame};
-use crate::opcode::OpCode::*;
-use crate::instance::{Instance, Instance::*, Type};
-use std::intrinsics::transmute;
 use crate::opcode::Chunk;
+use crate::math::OverflowPolicy;
 use std::rc::Rc;
-use std::collections::HashSet;
-use crate::string_pool::StringPool;
 use std::cell::RefCell;
 
 mod runtime;
@@ -19,30 +13,118 @@ mod vm;
 mod opcode;
 mod instance;
 mod string_pool;
+mod heap;
+mod exception;
+mod coroutine;
 mod math;
+mod decimal;
+mod format;
+mod bytecode;
+mod disassembler;
+mod assembler;
+mod verifier;
+mod error;
+mod natives;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let overflow_policy = match take_option(&mut args, "--overflow=") {
+        Some(name) => match OverflowPolicy::from_name(&name) {
+            Some(policy) => policy,
+            None => {
+                println!("Unknown overflow policy {}, expected error, wrap or saturate", name);
+                process::exit(1);
+            }
+        },
+        None => OverflowPolicy::Error
+    };
+    let pool_stats = take_flag(&mut args, "--pool-stats");
+    let gc_stats = take_flag(&mut args, "--gc-stats");
 
-    let mut vm = VM::new();
-
-    let mut chunk = Chunk::new();
-    chunk.add_const(0, UByte(255));
-
-    chunk.write(Get(true, 0));
-    chunk.write(Print);
-
-    vm.execute_chunk(Rc::new(chunk), Rc::new(RefCell::new(CallFrame::new())), vec![], vec![]);
-
-    /*
-    if args.len() >= 2 {
-        //engine::run(PathBuf::from(&args[1]))
+    if args.len() >= 3 && args[1] == "--disassemble" {
+        let vm = &mut VM::new();
+        let chunk = load_chunk(&args[2], vm);
+        print!("{}", disassembler::disassemble(&chunk, &vm.type_registry));
+    }
+    else if args.len() >= 4 && args[1] == "--assemble" {
+        let vm = &mut VM::new();
+        let chunk = load_chunk(&args[2], vm);
+        let bytes = match bytecode::write_chunk(&chunk, &vm.type_registry) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                println!("Could not assemble {}: {}", args[2], error);
+                process::exit(1);
+            }
+        };
+        if let Err(error) = fs::write(&args[3], bytes) {
+            println!("Could not write {}: {}", args[3], error);
+            process::exit(1);
+        }
+    }
+    else if args.len() >= 2 {
+        let vm = &mut VM::new();
+        vm.overflow_policy = overflow_policy;
+        let chunk = load_chunk(&args[1], vm);
+        if let Err(error) = vm.execute_chunk(Rc::new(chunk), Rc::new(RefCell::new(CallFrame::new())), vec![], vec![]) {
+            println!("Runtime error: {}", error);
+            process::exit(1);
+        }
+        if pool_stats {
+            vm.string_pool.sweep();
+            println!("String pool: {}", vm.string_pool.stats());
+        }
+        if gc_stats {
+            vm.collect_garbage();
+            println!("Heap: {}", vm.heap.stats());
+        }
     }
     else {
         println!("Please specify the path to the main script!");
+        println!("Usage: silicon [--overflow=error|wrap|saturate] [--pool-stats] [--gc-stats] <file>");
+        println!("       silicon --disassemble <file>");
+        println!("       silicon --assemble <file.sasm> <output>");
         process::exit(1);
     }
+}
 
-    println!("Program execution finished successfully.");
-    */
+// Removes the first argument starting with `prefix` and returns the rest of it.
+fn take_option(args: &mut Vec<String>, prefix: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg.starts_with(prefix))?;
+    Some(args.remove(position)[prefix.len()..].to_string())
+}
+
+// Removes `flag` from the arguments, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(position) => { args.remove(position); true },
+        None => false
+    }
+}
+
+// Loads a chunk from either a bytecode file or, for `.sasm` files, assembly.
+fn load_chunk(path: &str, vm: &mut VM) -> Chunk {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            println!("Could not read {}: {}", path, error);
+            process::exit(1);
+        }
+    };
+
+    let result = if path.ends_with(".sasm") {
+        match String::from_utf8(bytes) {
+            Ok(source) => assembler::assemble(&source, &mut vm.string_pool, &mut vm.type_registry).map_err(|error| error.to_string()),
+            Err(_) => Err("source is not valid UTF-8".to_string())
+        }
+    } else {
+        bytecode::read_chunk(&bytes, &mut vm.string_pool, &mut vm.type_registry).map_err(|error| error.to_string())
+    };
+
+    match result {
+        Ok(chunk) => chunk,
+        Err(error) => {
+            println!("Could not load {}: {}", path, error);
+            process::exit(1);
+        }
+    }
 }
@@ -1,170 +1,972 @@
 // Mathematical operations for Silicon VM
+//
+// Integer arithmetic is checked. When a result does not fit in its type, the
+// VM's `OverflowPolicy` decides whether that is an error, wraps around or
+// saturates at the type's bounds. Division by zero is always an error, and so
+// is shifting by a negative amount or by at least the width of the type,
+// whatever the policy. Right shifts are arithmetic for signed types and
+// logical for unsigned ones.
+//
+// Binary operations on two different numeric types first promote both
+// operands to a common type:
+//
+//   - two integers of the same signedness promote to the wider one;
+//   - a signed and an unsigned integer promote to the narrowest signed type
+//     wider than the unsigned one, or to Int128 for UInt128, which fails if
+//     the value does not fit;
+//   - a float and a float promote to Float64 unless both are Float32;
+//   - a float and an integer promote to Float32 if the float is a Float32
+//     and the integer has at most 16 bits, and to Float64 otherwise.
+//
+// BigInt and UBigInt are arbitrary-precision, so they never overflow. A BigInt
+// and any integer promote to BigInt, a UBigInt and an unsigned integer to
+// UBigInt and a UBigInt and a signed integer to BigInt. Either of them and a
+// float promote to Float64. A UBigInt result below zero is an error unless
+// the policy saturates, in which case it is zero; there is nothing to wrap
+// around to. Shifting a big integer by a negative amount or by more than
+// `u32::MAX` bits is an error.
+//
+// Decimals promote like integers when both operands are decimals, with the
+// scale of each value kept. A decimal and an integer or a float promote to the
+// decimal, so that money values never go through binary floats; floats convert
+// through their shortest representation. See `decimal` for their arithmetic.
+//
+// A Complex and any other numeric promote to Complex, the other operand going
+// through Float64 first. A Complex converts to another numeric type only when
+// its imaginary part is zero.
+//
+// Ordering comparisons do not promote their operands, since the common type
+// may not hold both or may round one of them; see `compare`.
+
+use std::rc::Rc;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::ops::{BitAnd, BitOr, BitXor, Not, Div, Rem, Shl, Shr};
+use num_bigint::{BigInt, BigUint};
+use num_traits::{CheckedSub, ToPrimitive, FromPrimitive, Signed, Zero};
+use crate::error::ErrorKind;
+use crate::instance::Instance;
+use crate::decimal;
+use crate::decimal::Decimal;
+use num_complex::Complex64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    Error,
+    Wrap,
+    Saturate
+}
+
+impl OverflowPolicy {
+    pub fn from_name(name: &str) -> Option<OverflowPolicy> {
+        match name {
+            "error" => Some(OverflowPolicy::Error),
+            "wrap" => Some(OverflowPolicy::Wrap),
+            "saturate" => Some(OverflowPolicy::Saturate),
+            _ => None
+        }
+    }
+}
+
+pub(crate) fn add<T>(left: T, right: T, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    let result = match left.checked_add(right) {
+        Some(result) => MathResult::Ok(result),
+        None if right > T::get_zero() => MathResult::Overflow,
+        None => MathResult::Underflow
+    };
+    result.resolve("add", policy, || left.wrapping_add(right))
+}
+
+pub(crate) fn subtract<T>(left: T, right: T, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    let result = match left.checked_sub(right) {
+        Some(result) => MathResult::Ok(result),
+        None if right < T::get_zero() => MathResult::Overflow,
+        None => MathResult::Underflow
+    };
+    result.resolve("subtract", policy, || left.wrapping_sub(right))
+}
+
+pub(crate) fn multiply<T>(left: T, right: T, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    let zero = T::get_zero();
+    let result = match left.checked_mul(right) {
+        Some(result) => MathResult::Ok(result),
+        None if (left < zero) == (right < zero) => MathResult::Overflow,
+        None => MathResult::Underflow
+    };
+    result.resolve("multiply", policy, || left.wrapping_mul(right))
+}
+
+pub(crate) fn divide<T>(left: T, right: T, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    // The only quotient that does not fit is `MIN / -1` for signed types.
+    let result = match left.checked_div(right) {
+        Some(result) => MathResult::Ok(result),
+        None if right == T::get_zero() => MathResult::DivisionByZero,
+        None => MathResult::Overflow
+    };
+    result.resolve("divide", policy, || left.wrapping_div(right))
+}
 
-use std::ops::{Add, Sub, Mul, Div};
-use std::i8::MAX;
+pub(crate) fn remainder<T>(left: T, right: T) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    // `MIN % -1` is 0, even though `checked_rem` refuses it for signed types.
+    match left.checked_rem(right) {
+        Some(result) => Ok(result),
+        None if right == T::get_zero() => Err(ErrorKind::DivisionByZero),
+        None => Ok(left.wrapping_rem(right))
+    }
+}
 
-fn add<T>(left: T, right: T) -> MathResult<T> where T: Add<Output = T> + Ord + MathExtensions
+pub(crate) fn shift_left<T>(value: T, amount: i128) -> Result<T, ErrorKind> where T: MathExtensions
 {
-    let min = T::get_min();
-    let max = T::get_max();
-    return MathResult::Ok(left + right)
+    Ok(value.shl(shift_amount::<T>(amount)?))
 }
 
-fn subtract<T>(left: T, right: T) -> MathResult<T> where T: Sub<Output = T> + Ord + MathExtensions
+pub(crate) fn shift_right<T>(value: T, amount: i128) -> Result<T, ErrorKind> where T: MathExtensions
 {
-    return MathResult::Ok(left - right)
+    Ok(value.shr(shift_amount::<T>(amount)?))
 }
 
-fn multiply<T>(left: T, right: T) -> MathResult<T> where T: Mul<Output = T> + Ord + MathExtensions
+fn shift_amount<T>(amount: i128) -> Result<u32, ErrorKind> where T: MathExtensions
 {
-    return MathResult::Ok(left * right)
+    if amount < 0 || amount >= T::BITS as i128 {
+        return Err(ErrorKind::InvalidShift { amount, type_name: Rc::new(T::TYPE_NAME.to_string()) })
+    }
+    Ok(amount as u32)
+}
+
+pub(crate) fn power<T>(base: T, exponent: u32, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    let result = match base.checked_pow(exponent) {
+        Some(result) => MathResult::Ok(result),
+        None if base < T::get_zero() && exponent % 2 == 1 => MathResult::Underflow,
+        None => MathResult::Overflow
+    };
+    result.resolve("raise", policy, || base.wrapping_pow(exponent))
+}
+
+pub(crate) fn negate<T>(operand: T, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: MathExtensions
+{
+    let result = match operand.checked_neg() {
+        Some(result) => MathResult::Ok(result),
+        None => MathResult::Overflow
+    };
+    result.resolve("negate", policy, || operand.wrapping_neg())
+}
+
+pub(crate) fn big_subtract<T>(left: T, right: T, policy: OverflowPolicy) -> Result<T, ErrorKind> where T: BigExtensions
+{
+    match left.checked_sub(&right) {
+        Some(result) => Ok(result),
+        None if policy == OverflowPolicy::Saturate => Ok(T::zero()),
+        None => Err(ErrorKind::IntegerOverflow { operation: "subtract", type_name: Rc::new(T::TYPE_NAME.to_string()) })
+    }
+}
+
+pub(crate) fn big_divide<T>(left: T, right: T) -> Result<T, ErrorKind> where T: BigExtensions
+{
+    if right.is_zero() {
+        return Err(ErrorKind::DivisionByZero)
+    }
+    Ok(left / right)
+}
+
+pub(crate) fn big_remainder<T>(left: T, right: T) -> Result<T, ErrorKind> where T: BigExtensions
+{
+    if right.is_zero() {
+        return Err(ErrorKind::DivisionByZero)
+    }
+    Ok(left % right)
 }
 
-fn divide<T>(left: T, right: T, zero: T) -> MathResult<T> where T: Div<Output = T> + Ord + MathExtensions
+pub(crate) fn big_shift_left<T>(value: T, amount: i128) -> Result<T, ErrorKind> where T: BigExtensions
 {
-    return MathResult::Ok(left / right)
+    Ok(value << big_shift_amount::<T>(amount)?)
+}
+
+pub(crate) fn big_shift_right<T>(value: T, amount: i128) -> Result<T, ErrorKind> where T: BigExtensions
+{
+    Ok(value >> big_shift_amount::<T>(amount)?)
+}
+
+fn big_shift_amount<T>(amount: i128) -> Result<usize, ErrorKind> where T: BigExtensions
+{
+    match u32::try_from(amount) {
+        Ok(amount) => Ok(amount as usize),
+        Err(_) => Err(ErrorKind::InvalidShift { amount, type_name: Rc::new(T::TYPE_NAME.to_string()) })
+    }
+}
+
+pub(crate) trait BigExtensions: Ord + Zero + CheckedSub + Div<Output = Self> + Rem<Output = Self> + Shl<usize, Output = Self> + Shr<usize, Output = Self> {
+    const TYPE_NAME: &'static str;
+}
+
+impl BigExtensions for BigInt {
+    const TYPE_NAME: &'static str = "silicon.lang.BigInt";
+}
+
+impl BigExtensions for BigUint {
+    const TYPE_NAME: &'static str = "silicon.lang.UBigInt";
 }
 
 pub enum MathResult<T> {
     Ok(T),
     Overflow,
-    Underflow
+    Underflow,
+    DivisionByZero
 }
 
-impl <T> MathResult<T> {
-    fn unwrap<F>(self, over: F, under: F) -> T
+impl <T> MathResult<T> where T: MathExtensions {
+    fn unwrap<F>(self, over: F, under: F) -> Result<T, ErrorKind>
         where F: Fn() -> T
     {
         match self {
-            MathResult::Overflow => over(),
-            MathResult::Underflow => under(),
-            MathResult::Ok(t) => t
+            MathResult::Overflow => Ok(over()),
+            MathResult::Underflow => Ok(under()),
+            MathResult::DivisionByZero => Err(ErrorKind::DivisionByZero),
+            MathResult::Ok(t) => Ok(t)
+        }
+    }
+
+    // Applies `policy` to a result that did not fit, using `wrapped` for the
+    // wrapped around value.
+    fn resolve<F>(self, operation: &'static str, policy: OverflowPolicy, wrapped: F) -> Result<T, ErrorKind>
+        where F: Fn() -> T
+    {
+        match (&self, policy) {
+            (MathResult::Overflow, OverflowPolicy::Error) | (MathResult::Underflow, OverflowPolicy::Error) =>
+                Err(ErrorKind::IntegerOverflow { operation, type_name: Rc::new(T::TYPE_NAME.to_string()) }),
+            (_, OverflowPolicy::Wrap) => self.unwrap(&wrapped, &wrapped),
+            _ => self.unwrap(T::get_max as fn() -> T, T::get_min)
         }
     }
 }
 
-trait MathExtensions {
+pub(crate) trait MathExtensions: Copy + PartialOrd + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self> {
+    const TYPE_NAME: &'static str;
+
+    const BITS: u32;
+
     fn get_min() -> Self;
 
     fn get_max() -> Self;
+
+    fn get_zero() -> Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    fn checked_sub(self, other: Self) -> Option<Self>;
+
+    fn checked_mul(self, other: Self) -> Option<Self>;
+
+    fn checked_div(self, other: Self) -> Option<Self>;
+
+    fn checked_pow(self, exponent: u32) -> Option<Self>;
+
+    fn checked_neg(self) -> Option<Self>;
+
+    fn checked_rem(self, other: Self) -> Option<Self>;
+
+    // Only called with amounts below `BITS`.
+    fn shl(self, amount: u32) -> Self;
+
+    fn shr(self, amount: u32) -> Self;
+
+    fn wrapping_add(self, other: Self) -> Self;
+
+    fn wrapping_sub(self, other: Self) -> Self;
+
+    fn wrapping_mul(self, other: Self) -> Self;
+
+    fn wrapping_div(self, other: Self) -> Self;
+
+    fn wrapping_pow(self, exponent: u32) -> Self;
+
+    fn wrapping_neg(self) -> Self;
+
+    fn wrapping_rem(self, other: Self) -> Self;
 }
 
-impl MathExtensions for i8 {
-    fn get_min() -> Self {
-        i8::min_value()
+macro_rules! impl_math_extensions {
+    ($($t:ty => $name:expr),*) => {
+        $(
+            impl MathExtensions for $t {
+                const TYPE_NAME: &'static str = $name;
+
+                const BITS: u32 = <$t>::BITS;
+
+                fn get_min() -> Self { <$t>::MIN }
+
+                fn get_max() -> Self { <$t>::MAX }
+
+                fn get_zero() -> Self { 0 }
+
+                fn checked_add(self, other: Self) -> Option<Self> { <$t>::checked_add(self, other) }
+
+                fn checked_sub(self, other: Self) -> Option<Self> { <$t>::checked_sub(self, other) }
+
+                fn checked_mul(self, other: Self) -> Option<Self> { <$t>::checked_mul(self, other) }
+
+                fn checked_div(self, other: Self) -> Option<Self> { <$t>::checked_div(self, other) }
+
+                fn checked_pow(self, exponent: u32) -> Option<Self> { <$t>::checked_pow(self, exponent) }
+
+                fn checked_neg(self) -> Option<Self> { <$t>::checked_neg(self) }
+
+                fn checked_rem(self, other: Self) -> Option<Self> { <$t>::checked_rem(self, other) }
+
+                fn shl(self, amount: u32) -> Self { self << amount }
+
+                fn shr(self, amount: u32) -> Self { self >> amount }
+
+                fn wrapping_add(self, other: Self) -> Self { <$t>::wrapping_add(self, other) }
+
+                fn wrapping_sub(self, other: Self) -> Self { <$t>::wrapping_sub(self, other) }
+
+                fn wrapping_mul(self, other: Self) -> Self { <$t>::wrapping_mul(self, other) }
+
+                // Division by zero never gets here, nor below in `wrapping_rem`,
+                // since it is an error under every policy.
+                fn wrapping_div(self, other: Self) -> Self { <$t>::wrapping_div(self, other) }
+
+                fn wrapping_pow(self, exponent: u32) -> Self { <$t>::wrapping_pow(self, exponent) }
+
+                fn wrapping_neg(self) -> Self { <$t>::wrapping_neg(self) }
+
+                fn wrapping_rem(self, other: Self) -> Self { <$t>::wrapping_rem(self, other) }
+            }
+        )*
+    };
+}
+
+impl_math_extensions!(
+    i8 => "silicon.lang.Byte",
+    u8 => "silicon.lang.UByte",
+    i16 => "silicon.lang.Int16",
+    u16 => "silicon.lang.UInt16",
+    i32 => "silicon.lang.Int32",
+    u32 => "silicon.lang.UInt32",
+    i64 => "silicon.lang.Int64",
+    u64 => "silicon.lang.UInt64",
+    i128 => "silicon.lang.Int128",
+    u128 => "silicon.lang.UInt128"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NumericKind {
+    Byte,
+    UByte,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Int128,
+    UInt128,
+    Float32,
+    Float64,
+    BigInt,
+    UBigInt,
+    Decimal16,
+    UDecimal16,
+    Decimal32,
+    UDecimal32,
+    Decimal64,
+    UDecimal64,
+    Decimal128,
+    UDecimal128,
+    Complex,
+    // Only takes part in conversions, as a code point.
+    Char
+}
+
+impl NumericKind {
+    pub(crate) fn of(instance: &Instance) -> Option<NumericKind> {
+        match instance {
+            Instance::Byte(_) => Some(NumericKind::Byte),
+            Instance::UByte(_) => Some(NumericKind::UByte),
+            Instance::Int16(_) => Some(NumericKind::Int16),
+            Instance::UInt16(_) => Some(NumericKind::UInt16),
+            Instance::Int32(_) => Some(NumericKind::Int32),
+            Instance::UInt32(_) => Some(NumericKind::UInt32),
+            Instance::Int64(_) => Some(NumericKind::Int64),
+            Instance::UInt64(_) => Some(NumericKind::UInt64),
+            Instance::Int128(_) => Some(NumericKind::Int128),
+            Instance::UInt128(_) => Some(NumericKind::UInt128),
+            Instance::Float32(_) => Some(NumericKind::Float32),
+            Instance::Float64(_) => Some(NumericKind::Float64),
+            Instance::BigInt(_) => Some(NumericKind::BigInt),
+            Instance::UBigInt(_) => Some(NumericKind::UBigInt),
+            Instance::Decimal16(_) => Some(NumericKind::Decimal16),
+            Instance::UDecimal16(_) => Some(NumericKind::UDecimal16),
+            Instance::Decimal32(_) => Some(NumericKind::Decimal32),
+            Instance::UDecimal32(_) => Some(NumericKind::UDecimal32),
+            Instance::Decimal64(_) => Some(NumericKind::Decimal64),
+            Instance::UDecimal64(_) => Some(NumericKind::UDecimal64),
+            Instance::Decimal128(_) => Some(NumericKind::Decimal128),
+            Instance::UDecimal128(_) => Some(NumericKind::UDecimal128),
+            Instance::Complex(_) => Some(NumericKind::Complex),
+            Instance::Char(_) => Some(NumericKind::Char),
+            _ => None
+        }
     }
 
-    fn get_max() -> Self {
-        i8::max_value()
+    pub(crate) fn from_name(name: &str) -> Option<NumericKind> {
+        match name {
+            "silicon.lang.Byte" => Some(NumericKind::Byte),
+            "silicon.lang.UByte" => Some(NumericKind::UByte),
+            "silicon.lang.Int16" => Some(NumericKind::Int16),
+            "silicon.lang.UInt16" => Some(NumericKind::UInt16),
+            "silicon.lang.Int32" => Some(NumericKind::Int32),
+            "silicon.lang.UInt32" => Some(NumericKind::UInt32),
+            "silicon.lang.Int64" => Some(NumericKind::Int64),
+            "silicon.lang.UInt64" => Some(NumericKind::UInt64),
+            "silicon.lang.Int128" => Some(NumericKind::Int128),
+            "silicon.lang.UInt128" => Some(NumericKind::UInt128),
+            "silicon.lang.Float32" => Some(NumericKind::Float32),
+            "silicon.lang.Float64" => Some(NumericKind::Float64),
+            "silicon.lang.BigInt" => Some(NumericKind::BigInt),
+            "silicon.lang.UBigInt" => Some(NumericKind::UBigInt),
+            "silicon.lang.Decimal16" => Some(NumericKind::Decimal16),
+            "silicon.lang.UDecimal16" => Some(NumericKind::UDecimal16),
+            "silicon.lang.Decimal32" => Some(NumericKind::Decimal32),
+            "silicon.lang.UDecimal32" => Some(NumericKind::UDecimal32),
+            "silicon.lang.Decimal64" => Some(NumericKind::Decimal64),
+            "silicon.lang.UDecimal64" => Some(NumericKind::UDecimal64),
+            "silicon.lang.Decimal128" => Some(NumericKind::Decimal128),
+            "silicon.lang.UDecimal128" => Some(NumericKind::UDecimal128),
+            "silicon.lang.Complex" => Some(NumericKind::Complex),
+            "silicon.lang.Char" => Some(NumericKind::Char),
+            _ => None
+        }
     }
-}
 
-impl MathExtensions for u8 {
-    fn get_min() -> Self {
-        u8::min_value()
+    fn name(self) -> &'static str {
+        match self {
+            NumericKind::Byte => "silicon.lang.Byte",
+            NumericKind::UByte => "silicon.lang.UByte",
+            NumericKind::Int16 => "silicon.lang.Int16",
+            NumericKind::UInt16 => "silicon.lang.UInt16",
+            NumericKind::Int32 => "silicon.lang.Int32",
+            NumericKind::UInt32 => "silicon.lang.UInt32",
+            NumericKind::Int64 => "silicon.lang.Int64",
+            NumericKind::UInt64 => "silicon.lang.UInt64",
+            NumericKind::Int128 => "silicon.lang.Int128",
+            NumericKind::UInt128 => "silicon.lang.UInt128",
+            NumericKind::Float32 => "silicon.lang.Float32",
+            NumericKind::Float64 => "silicon.lang.Float64",
+            NumericKind::BigInt => "silicon.lang.BigInt",
+            NumericKind::UBigInt => "silicon.lang.UBigInt",
+            NumericKind::Decimal16 => "silicon.lang.Decimal16",
+            NumericKind::UDecimal16 => "silicon.lang.UDecimal16",
+            NumericKind::Decimal32 => "silicon.lang.Decimal32",
+            NumericKind::UDecimal32 => "silicon.lang.UDecimal32",
+            NumericKind::Decimal64 => "silicon.lang.Decimal64",
+            NumericKind::UDecimal64 => "silicon.lang.UDecimal64",
+            NumericKind::Decimal128 => "silicon.lang.Decimal128",
+            NumericKind::UDecimal128 => "silicon.lang.UDecimal128",
+            NumericKind::Complex => "silicon.lang.Complex",
+            NumericKind::Char => "silicon.lang.Char"
+        }
     }
 
-    fn get_max() -> Self {
-        u8::max_value()
+    fn bits(self) -> u32 {
+        match self {
+            NumericKind::Byte | NumericKind::UByte => 8,
+            NumericKind::Int16 | NumericKind::UInt16 | NumericKind::Decimal16 | NumericKind::UDecimal16 => 16,
+            NumericKind::Int32 | NumericKind::UInt32 | NumericKind::Float32 | NumericKind::Char
+                | NumericKind::Decimal32 | NumericKind::UDecimal32 => 32,
+            NumericKind::Int64 | NumericKind::UInt64 | NumericKind::Float64 | NumericKind::Decimal64 | NumericKind::UDecimal64 => 64,
+            NumericKind::Int128 | NumericKind::UInt128 | NumericKind::Decimal128 | NumericKind::UDecimal128 | NumericKind::Complex => 128,
+            NumericKind::BigInt | NumericKind::UBigInt => u32::MAX
+        }
     }
-}
 
-impl MathExtensions for i16 {
-    fn get_min() -> Self {
-        i16::min_value()
+    fn is_float(self) -> bool {
+        matches!(self, NumericKind::Float32 | NumericKind::Float64)
     }
 
-    fn get_max() -> Self {
-        i16::max_value()
+    fn is_signed(self) -> bool {
+        matches!(self, NumericKind::Byte | NumericKind::Int16 | NumericKind::Int32 | NumericKind::Int64 | NumericKind::Int128 | NumericKind::BigInt
+            | NumericKind::Decimal16 | NumericKind::Decimal32 | NumericKind::Decimal64 | NumericKind::Decimal128) || self.is_float()
     }
-}
 
-impl MathExtensions for u16 {
-    fn get_min() -> Self {
-        u16::min_value()
+    fn is_decimal(self) -> bool {
+        matches!(self, NumericKind::Decimal16 | NumericKind::UDecimal16 | NumericKind::Decimal32 | NumericKind::UDecimal32
+            | NumericKind::Decimal64 | NumericKind::UDecimal64 | NumericKind::Decimal128 | NumericKind::UDecimal128)
     }
 
-    fn get_max() -> Self {
-        u16::max_value()
+    // The integer kind backing a decimal kind, and back.
+    fn to_integer(self) -> NumericKind {
+        match self {
+            NumericKind::Decimal16 => NumericKind::Int16,
+            NumericKind::UDecimal16 => NumericKind::UInt16,
+            NumericKind::Decimal32 => NumericKind::Int32,
+            NumericKind::UDecimal32 => NumericKind::UInt32,
+            NumericKind::Decimal64 => NumericKind::Int64,
+            NumericKind::UDecimal64 => NumericKind::UInt64,
+            NumericKind::Decimal128 => NumericKind::Int128,
+            NumericKind::UDecimal128 => NumericKind::UInt128,
+            kind => kind
+        }
+    }
+
+    fn to_decimal(self) -> NumericKind {
+        match self {
+            NumericKind::Byte | NumericKind::Int16 => NumericKind::Decimal16,
+            NumericKind::UByte | NumericKind::UInt16 => NumericKind::UDecimal16,
+            NumericKind::Int32 => NumericKind::Decimal32,
+            NumericKind::UInt32 => NumericKind::UDecimal32,
+            NumericKind::Int64 => NumericKind::Decimal64,
+            NumericKind::UInt64 => NumericKind::UDecimal64,
+            NumericKind::Int128 => NumericKind::Decimal128,
+            NumericKind::UInt128 => NumericKind::UDecimal128,
+            kind => kind
+        }
     }
-}
 
-impl MathExtensions for i32 {
-    fn get_min() -> Self {
-        i32::min_value()
+    fn is_big(self) -> bool {
+        matches!(self, NumericKind::BigInt | NumericKind::UBigInt)
     }
 
-    fn get_max() -> Self {
-        i32::max_value()
+    fn signed_with_bits(bits: u32) -> NumericKind {
+        match bits {
+            0..=8 => NumericKind::Byte,
+            9..=16 => NumericKind::Int16,
+            17..=32 => NumericKind::Int32,
+            33..=64 => NumericKind::Int64,
+            _ => NumericKind::Int128
+        }
     }
 }
 
-impl MathExtensions for u32 {
-    fn get_min() -> Self {
-        u32::min_value()
+// The type two numeric operands of the given types are promoted to, following
+// the rules at the top of this file.
+fn promoted_kind(left: NumericKind, right: NumericKind) -> NumericKind {
+    if left == right {
+        return left
+    }
+    if left == NumericKind::Complex || right == NumericKind::Complex {
+        return NumericKind::Complex
+    }
+    match (left.is_decimal(), right.is_decimal()) {
+        (true, true) => return promoted_kind(left.to_integer(), right.to_integer()).to_decimal(),
+        (true, false) => return left,
+        (false, true) => return right,
+        (false, false) => {}
+    }
+    if left.is_big() || right.is_big() {
+        return match (left.is_float() || right.is_float(), left.is_signed() || right.is_signed()) {
+            (true, _) => NumericKind::Float64,
+            (false, true) => NumericKind::BigInt,
+            (false, false) => NumericKind::UBigInt
+        }
+    }
+    match (left.is_float(), right.is_float()) {
+        (true, true) => NumericKind::Float64,
+        (true, false) | (false, true) => {
+            let (float, integer) = if left.is_float() { (left, right) } else { (right, left) };
+            if float == NumericKind::Float32 && integer.bits() <= 16 { NumericKind::Float32 } else { NumericKind::Float64 }
+        },
+        (false, false) if left.is_signed() == right.is_signed() => if left.bits() >= right.bits() { left } else { right },
+        (false, false) => {
+            let (signed, unsigned) = if left.is_signed() { (left, right) } else { (right, left) };
+            if signed.bits() > unsigned.bits() { signed } else { NumericKind::signed_with_bits(unsigned.bits() + 1) }
+        }
     }
+}
 
-    fn get_max() -> Self {
-        u32::max_value()
+// Raises a complex number to a complex power. Whole real exponents multiply
+// the base out so that, say, squaring 1+1i gives exactly 0+2i.
+pub(crate) fn complex_power(base: Complex64, exponent: Complex64) -> Complex64 {
+    let whole = exponent.re.fract() == 0.0 && (i32::MIN as f64..=i32::MAX as f64).contains(&exponent.re);
+    match exponent.im == 0.0 && whole {
+        true => base.powi(exponent.re as i32),
+        false => base.powc(exponent)
     }
 }
 
-impl MathExtensions for i64 {
-    fn get_min() -> Self {
-        i64::min_value()
+// Converts two numeric operands of different types to their common type.
+// Anything else, including chars, is returned unchanged.
+pub(crate) fn promote(left: Instance, right: Instance) -> Result<(Instance, Instance), ErrorKind> {
+    match (NumericKind::of(&left), NumericKind::of(&right)) {
+        (Some(left_kind), Some(right_kind)) if left_kind != right_kind && left_kind != NumericKind::Char && right_kind != NumericKind::Char => {
+            let kind = promoted_kind(left_kind, right_kind);
+            Ok((convert(&left, kind, true)?, convert(&right, kind, true)?))
+        },
+        _ => Ok((left, right))
     }
+}
 
-    fn get_max() -> Self {
-        i64::max_value()
+// Orders two real numbers by their exact values, without promoting them to a
+// common type first, which might not hold both: an Int32 is less than any
+// UInt128 above `i128::MAX`, and an Int64 of 2^53 + 1 is greater than a
+// Float64 of 2^53. A decimal and a float are the exception, compared as the
+// decimal that the float promotes to. None means that one of them is NaN.
+pub(crate) fn compare(left: &Instance, right: &Instance) -> Result<Option<Ordering>, ErrorKind> {
+    let unsupported = || ErrorKind::UnsupportedOperands { operation: "compare", left: left.get_canonical_name(), right: right.get_canonical_name() };
+    if matches!(left, Instance::Char(_)) || matches!(right, Instance::Char(_)) {
+        return Err(unsupported())
     }
+    let ordering = match (widen(left).ok_or_else(unsupported)?, widen(right).ok_or_else(unsupported)?) {
+        (Wide::Float(left), Wide::Float(right)) => left.partial_cmp(&right),
+        (Wide::Float(float), other) => compare_to_float(other, float).map(Ordering::reverse),
+        (other, Wide::Float(float)) => compare_to_float(other, float),
+        (left, right) => Some(decimal::compare_unscaled(exact(left), exact(right)))
+    };
+    Ok(ordering)
 }
 
-impl MathExtensions for u64 {
-    fn get_min() -> Self {
-        u64::min_value()
+// An integer or decimal as an unscaled value and its scale.
+fn exact(wide: Wide) -> (BigInt, u8) {
+    match wide {
+        Wide::Signed(value) => (BigInt::from(value), 0),
+        Wide::Unsigned(value) => (BigInt::from(value), 0),
+        Wide::Big(value) => (value, 0),
+        Wide::Decimal(value, scale) => (value, scale),
+        Wide::Float(_) => unreachable!("floats are compared separately")
     }
+}
 
-    fn get_max() -> Self {
-        u64::max_value()
+fn compare_to_float(value: Wide, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None
+    }
+    if float.is_infinite() {
+        return Some(if float > 0.0 { Ordering::Less } else { Ordering::Greater })
+    }
+    if let Wide::Decimal(value, scale) = value {
+        return Some(decimal::compare_with_float((value, scale), float))
+    }
+    // A finite float's floor is a whole number that a BigInt holds exactly.
+    let floor = BigInt::from_f64(float.floor()).expect("finite floats have a whole floor");
+    match exact(value).0.cmp(&floor) {
+        Ordering::Equal if float.fract() != 0.0 => Some(Ordering::Less),
+        ordering => Some(ordering)
     }
 }
 
-impl MathExtensions for i128 {
-    fn get_min() -> Self {
-        i128::min_value()
+// Reads a shift amount, which may be an integer of any type.
+pub(crate) fn to_shift_amount(instance: &Instance) -> Result<i128, ErrorKind> {
+    match (NumericKind::of(instance), widen(instance)) {
+        (Some(kind), Some(Wide::Signed(amount))) if kind != NumericKind::Char => Ok(amount),
+        (Some(kind), Some(Wide::Unsigned(amount))) if kind != NumericKind::Char => Ok(i128::try_from(amount).unwrap_or(i128::MAX)),
+        (_, Some(Wide::Big(amount))) => Ok(amount.to_i128().unwrap_or(if amount.is_negative() { i128::MIN } else { i128::MAX })),
+        _ => Err(ErrorKind::UnsupportedOperand { operation: "shift by", operand: instance.get_canonical_name() })
     }
+}
 
-    fn get_max() -> Self {
-        i128::max_value()
+// A numeric value widened so that every conversion can start from it.
+enum Wide {
+    Signed(i128),
+    Unsigned(u128),
+    Float(f64),
+    Big(BigInt),
+    // An unscaled value and its scale.
+    Decimal(BigInt, u8)
+}
+
+impl Wide {
+    fn from_decimal((value, scale): (BigInt, u8)) -> Wide {
+        Wide::Decimal(value, scale)
     }
 }
 
-impl MathExtensions for u128 {
-    fn get_min() -> Self {
-        u128::min_value()
+fn widen(instance: &Instance) -> Option<Wide> {
+    let wide = match instance {
+        Instance::Byte(value) => Wide::Signed(*value as i128),
+        Instance::UByte(value) => Wide::Unsigned(*value as u128),
+        Instance::Int16(value) => Wide::Signed(*value as i128),
+        Instance::UInt16(value) => Wide::Unsigned(*value as u128),
+        Instance::Int32(value) => Wide::Signed(*value as i128),
+        Instance::UInt32(value) => Wide::Unsigned(*value as u128),
+        Instance::Int64(value) => Wide::Signed(*value as i128),
+        Instance::UInt64(value) => Wide::Unsigned(*value as u128),
+        Instance::Int128(value) => Wide::Signed(*value),
+        Instance::UInt128(value) => Wide::Unsigned(*value),
+        Instance::Float32(value) => Wide::Float(*value as f64),
+        Instance::Float64(value) => Wide::Float(*value),
+        Instance::BigInt(value) => Wide::Big(value.clone()),
+        Instance::UBigInt(value) => Wide::Big(BigInt::from(value.clone())),
+        Instance::Decimal16(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::UDecimal16(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::Decimal32(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::UDecimal32(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::Decimal64(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::UDecimal64(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::Decimal128(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::UDecimal128(value) => Wide::from_decimal(value.to_unscaled()),
+        Instance::Char(value) => Wide::Unsigned(*value as u128),
+        _ => return None
+    };
+    Some(wide)
+}
+
+// 2^127 and 2^128, the first floats past the ends of the 128-bit integers.
+const TWO_POW_127: f64 = 170141183460469231731687303715884105728.0;
+const TWO_POW_128: f64 = 340282366920938463463374607431768211456.0;
+
+// Converts `instance` to `target`, failing if that loses its value: integers
+// and chars must fit in the target, floats must be whole numbers to become
+// integers and integers must be exactly representable to become floats.
+// Narrowing a Float64 to a Float32 may round, but must not overflow. With
+// `round_to_float`, as used by promotion, integers may round to become floats.
+pub(crate) fn convert(instance: &Instance, target: NumericKind, round_to_float: bool) -> Result<Instance, ErrorKind> {
+    let lost = || ErrorKind::LossyConversion { value: Rc::new(instance.to_string()), target: Rc::new(target.name().to_string()) };
+    match (instance, target) {
+        (Instance::Complex(_), NumericKind::Complex) => return Ok(instance.clone()),
+        (_, NumericKind::Complex) => {
+            return match convert(instance, NumericKind::Float64, round_to_float)? {
+                Instance::Float64(real) => Ok(Instance::Complex(Complex64::new(real, 0.0))),
+                _ => Err(lost())
+            }
+        },
+        (Instance::Complex(complex), _) if complex.im == 0.0 => {
+            return convert(&Instance::Float64(complex.re), target, round_to_float).map_err(|_| lost())
+        },
+        (Instance::Complex(_), _) => return Err(lost()),
+        _ => {}
     }
 
-    fn get_max() -> Self {
-        u128::max_value()
+    let wide = match widen(instance) {
+        Some(wide) => wide,
+        None => return Err(ErrorKind::UnsupportedOperand { operation: "convert", operand: instance.get_canonical_name() })
+    };
+
+    if target.is_float() {
+        let value = match &wide {
+            Wide::Float(value) => *value,
+            Wide::Signed(value) => {
+                let float = *value as f64;
+                if !round_to_float && (float >= TWO_POW_127 || float as i128 != *value) {
+                    return Err(lost())
+                }
+                float
+            },
+            Wide::Unsigned(value) => {
+                let float = *value as f64;
+                if !round_to_float && (float >= TWO_POW_128 || float as u128 != *value) {
+                    return Err(lost())
+                }
+                float
+            },
+            Wide::Big(value) => {
+                // Too large a value becomes infinite, which is lost even when rounding.
+                let float = value.to_f64().unwrap_or(f64::INFINITY);
+                if float.is_infinite() || (!round_to_float && BigInt::from_f64(float).as_ref() != Some(value)) {
+                    return Err(lost())
+                }
+                float
+            },
+            Wide::Decimal(value, scale) => {
+                let float = decimal::to_float(value, *scale);
+                let exact = decimal::from_float(float).is_some_and(|float| decimal::same_value(float, (value.clone(), *scale)));
+                if float.is_infinite() || (!round_to_float && !exact) {
+                    return Err(lost())
+                }
+                float
+            }
+        };
+        return match target {
+            NumericKind::Float32 => {
+                let narrowed = value as f32;
+                if narrowed.is_infinite() && value.is_finite() {
+                    return Err(lost())
+                }
+                if !round_to_float && !matches!(wide, Wide::Float(_) | Wide::Decimal(_, _)) && narrowed as f64 != value {
+                    return Err(lost())
+                }
+                // Decimals are compared with the shortest representation of the
+                // Float32, as they were with that of the Float64.
+                if let (false, Wide::Decimal(decimal, scale)) = (round_to_float, &wide) {
+                    if !decimal::parse_unscaled(&narrowed.to_string()).is_some_and(|float| decimal::same_value(float, (decimal.clone(), *scale))) {
+                        return Err(lost())
+                    }
+                }
+                Ok(Instance::Float32(narrowed))
+            },
+            _ => Ok(Instance::Float64(value))
+        }
+    }
+
+    if target.is_decimal() {
+        let (value, scale) = match wide {
+            Wide::Signed(value) => (BigInt::from(value), 0),
+            Wide::Unsigned(value) => (BigInt::from(value), 0),
+            Wide::Big(value) => (value, 0),
+            Wide::Decimal(value, scale) => (value, scale),
+            Wide::Float(value) => decimal::from_float(value).ok_or_else(lost)?
+        };
+        return narrow_decimal(value, scale, target).ok_or_else(lost)
+    }
+
+    // Decimals become integers only when they have no fraction.
+    let wide = match wide {
+        Wide::Decimal(value, scale) => Wide::Big(decimal::to_whole(&value, scale).ok_or_else(lost)?),
+        wide => wide
+    };
+
+    if target.is_big() {
+        let value = match wide {
+            Wide::Signed(value) => BigInt::from(value),
+            Wide::Unsigned(value) => BigInt::from(value),
+            Wide::Big(value) => value,
+            Wide::Float(value) => match value.fract() == 0.0 {
+                true => BigInt::from_f64(value).ok_or_else(lost)?,
+                false => return Err(lost())
+            },
+            Wide::Decimal(_, _) => return Err(lost())
+        };
+        return match target {
+            NumericKind::BigInt => Ok(Instance::BigInt(value)),
+            _ => value.to_biguint().map(Instance::UBigInt).ok_or_else(lost)
+        }
     }
+
+    // Every other integer target is reached through i128 or u128.
+    let wide = match wide {
+        Wide::Big(value) => match (value.to_i128(), value.to_u128()) {
+            (Some(value), _) => Wide::Signed(value),
+            (None, Some(value)) => Wide::Unsigned(value),
+            (None, None) => return Err(lost())
+        },
+        Wide::Float(value) => {
+            if value.fract() != 0.0 || !value.is_finite() {
+                return Err(lost())
+            }
+            if (0.0..TWO_POW_128).contains(&value) {
+                Wide::Unsigned(value as u128)
+            } else if (-TWO_POW_127..0.0).contains(&value) {
+                Wide::Signed(value as i128)
+            } else {
+                return Err(lost())
+            }
+        },
+        wide => wide
+    };
+    let converted = match (target, wide) {
+        (NumericKind::Char, Wide::Signed(value)) => u32::try_from(value).ok().and_then(char::from_u32).map(Instance::Char),
+        (NumericKind::Char, Wide::Unsigned(value)) => u32::try_from(value).ok().and_then(char::from_u32).map(Instance::Char),
+        (_, Wide::Signed(value)) => narrow_signed(value, target),
+        (_, Wide::Unsigned(value)) => narrow_unsigned(value, target),
+        (_, Wide::Float(_)) | (_, Wide::Big(_)) | (_, Wide::Decimal(_, _)) => None
+    };
+    converted.ok_or_else(lost)
 }
 
-impl MathExtensions for f32 {
-    fn get_min() -> Self {
-        std::f32::MIN
+fn narrow_signed(value: i128, target: NumericKind) -> Option<Instance> {
+    match target {
+        NumericKind::Byte => i8::try_from(value).ok().map(Instance::Byte),
+        NumericKind::UByte => u8::try_from(value).ok().map(Instance::UByte),
+        NumericKind::Int16 => i16::try_from(value).ok().map(Instance::Int16),
+        NumericKind::UInt16 => u16::try_from(value).ok().map(Instance::UInt16),
+        NumericKind::Int32 => i32::try_from(value).ok().map(Instance::Int32),
+        NumericKind::UInt32 => u32::try_from(value).ok().map(Instance::UInt32),
+        NumericKind::Int64 => i64::try_from(value).ok().map(Instance::Int64),
+        NumericKind::UInt64 => u64::try_from(value).ok().map(Instance::UInt64),
+        NumericKind::Int128 => Some(Instance::Int128(value)),
+        NumericKind::UInt128 => u128::try_from(value).ok().map(Instance::UInt128),
+        _ => None
     }
+}
 
-    fn get_max() -> Self {
-        std::f32::MAX
+fn narrow_decimal(value: BigInt, scale: u8, target: NumericKind) -> Option<Instance> {
+    match target {
+        NumericKind::Decimal16 => Decimal::fit(value, scale).map(Instance::Decimal16),
+        NumericKind::UDecimal16 => Decimal::fit(value, scale).map(Instance::UDecimal16),
+        NumericKind::Decimal32 => Decimal::fit(value, scale).map(Instance::Decimal32),
+        NumericKind::UDecimal32 => Decimal::fit(value, scale).map(Instance::UDecimal32),
+        NumericKind::Decimal64 => Decimal::fit(value, scale).map(Instance::Decimal64),
+        NumericKind::UDecimal64 => Decimal::fit(value, scale).map(Instance::UDecimal64),
+        NumericKind::Decimal128 => Decimal::fit(value, scale).map(Instance::Decimal128),
+        NumericKind::UDecimal128 => Decimal::fit(value, scale).map(Instance::UDecimal128),
+        _ => None
     }
 }
 
-impl MathExtensions for f64 {
-    fn get_min() -> Self {
-        std::f64::MIN
+fn narrow_unsigned(value: u128, target: NumericKind) -> Option<Instance> {
+    match target {
+        NumericKind::Byte => i8::try_from(value).ok().map(Instance::Byte),
+        NumericKind::UByte => u8::try_from(value).ok().map(Instance::UByte),
+        NumericKind::Int16 => i16::try_from(value).ok().map(Instance::Int16),
+        NumericKind::UInt16 => u16::try_from(value).ok().map(Instance::UInt16),
+        NumericKind::Int32 => i32::try_from(value).ok().map(Instance::Int32),
+        NumericKind::UInt32 => u32::try_from(value).ok().map(Instance::UInt32),
+        NumericKind::Int64 => i64::try_from(value).ok().map(Instance::Int64),
+        NumericKind::UInt64 => u64::try_from(value).ok().map(Instance::UInt64),
+        NumericKind::Int128 => i128::try_from(value).ok().map(Instance::Int128),
+        NumericKind::UInt128 => Some(Instance::UInt128(value)),
+        _ => None
     }
+}
 
-    fn get_max() -> Self {
-        std::f64::MAX
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_remainder_minus_one_is_zero() {
+        assert!(matches!(remainder(i8::MIN, -1), Ok(0)));
+        assert!(matches!(remainder(i32::MIN, -1), Ok(0)));
+        assert!(matches!(remainder(i128::MIN, -1), Ok(0)));
+        assert!(matches!(remainder(-7i32, 2), Ok(-1)));
+        assert!(matches!(remainder(7u32, 0), Err(ErrorKind::DivisionByZero)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn min_divided_by_minus_one_follows_the_policy() {
+        assert!(matches!(divide(i32::MIN, -1, OverflowPolicy::Error), Err(ErrorKind::IntegerOverflow { operation: "divide", .. })));
+        assert!(matches!(divide(i32::MIN, -1, OverflowPolicy::Wrap), Ok(i32::MIN)));
+        assert!(matches!(divide(i32::MIN, -1, OverflowPolicy::Saturate), Ok(i32::MAX)));
+        for policy in [OverflowPolicy::Error, OverflowPolicy::Wrap, OverflowPolicy::Saturate] {
+            assert!(matches!(divide(1i64, 0, policy), Err(ErrorKind::DivisionByZero)));
+        }
+    }
+
+    #[test]
+    fn overflow_saturates_in_the_direction_it_went() {
+        assert!(matches!(add(i16::MAX, 1, OverflowPolicy::Saturate), Ok(i16::MAX)));
+        assert!(matches!(subtract(i16::MIN, 1, OverflowPolicy::Saturate), Ok(i16::MIN)));
+        assert!(matches!(multiply(i16::MIN, 2, OverflowPolicy::Saturate), Ok(i16::MIN)));
+        assert!(matches!(subtract(0u8, 1, OverflowPolicy::Saturate), Ok(0)));
+        assert!(matches!(add(u8::MAX, 1, OverflowPolicy::Wrap), Ok(0)));
+        assert!(matches!(negate(i64::MIN, OverflowPolicy::Error), Err(ErrorKind::IntegerOverflow { .. })));
+        assert!(matches!(negate(i64::MIN, OverflowPolicy::Saturate), Ok(i64::MAX)));
+    }
+
+    #[test]
+    fn comparisons_are_exact() {
+        let above_i128 = Instance::UInt128(1 << 127);
+        assert!(matches!(compare(&Instance::Int32(0), &above_i128), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&Instance::Int64(-1), &Instance::UInt64(u64::MAX)), Ok(Some(Ordering::Less))));
+
+        // 2^53 + 1 rounds to 2^53 as a Float64.
+        let float = Instance::Float64(9007199254740992.0);
+        assert!(matches!(compare(&Instance::Int64(9007199254740993), &float), Ok(Some(Ordering::Greater))));
+        assert!(matches!(compare(&float, &Instance::Int64(9007199254740993)), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&Instance::Int32(2), &Instance::Float64(2.5)), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&Instance::Int32(-3), &Instance::Float64(-2.5)), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&Instance::Int32(i32::MAX), &Instance::Float64(f64::INFINITY)), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&Instance::Int32(0), &Instance::Float64(f64::NAN)), Ok(None)));
+
+        let price = Instance::Decimal64(Decimal::new(1999, 2));
+        assert!(matches!(compare(&price, &Instance::BigInt(BigInt::from(20))), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&price, &Instance::Decimal32(Decimal::new(19990, 3))), Ok(Some(Ordering::Equal))));
+
+        // Floats with more digits after the point than a decimal can hold.
+        let zero = Instance::Decimal64(Decimal::new(0, 2));
+        assert!(matches!(compare(&zero, &Instance::Float64(1e-50)), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&Instance::Float64(-5e-324), &zero), Ok(Some(Ordering::Less))));
+        assert!(matches!(compare(&price, &Instance::Float64(19.99)), Ok(Some(Ordering::Equal))));
+        assert!(matches!(compare(&price, &Instance::Float64(1e300)), Ok(Some(Ordering::Less))));
+    }
+
+    #[test]
+    fn chars_do_not_compare_with_numbers() {
+        assert!(matches!(compare(&Instance::Char('a'), &Instance::Int32(97)), Err(ErrorKind::UnsupportedOperands { operation: "compare", .. })));
+    }
+}
@@ -0,0 +1,210 @@
+// Native functions every VM starts with.
+
+use std::rc::Rc;
+use std::convert::TryFrom;
+use crate::instance::{Instance, Function, FunctionBody};
+use crate::coroutine::Coroutine;
+use crate::runtime::VM;
+use crate::error::ErrorKind;
+use num_complex::Complex64;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub(crate) fn register_core(vm: &mut VM) {
+    define(vm, "silicon.lang.Object.print", &["silicon.lang.Object"], "silicon.lang.Void", |_, args| {
+        println!("{}", args[0]);
+        Ok(Instance::Void)
+    });
+
+    define(vm, "silicon.lang.Object.toString", &["silicon.lang.Object"], "silicon.lang.String", |vm, args| {
+        let string = match &args[0] {
+            Instance::Str(string) => return Ok(Instance::Str(Rc::clone(string))),
+            Instance::Char(c) => c.to_string(),
+            instance => instance.to_string()
+        };
+        Ok(Instance::Str(vm.string_pool.pool_string(string)))
+    });
+
+    // Runs the garbage collector and returns the number of objects it freed.
+    define(vm, "silicon.lang.System.gc", &[], "silicon.lang.Int64", |vm, _| {
+        Ok(Instance::Int64(vm.collect_garbage() as i64))
+    });
+
+    // Creates an exception with the given message, ready to be thrown.
+    define(vm, "silicon.lang.Exception.new", &["silicon.lang.String"], "silicon.lang.Exception", |vm, args| {
+        let class = Rc::clone(&vm.exceptions.exception);
+        Ok(vm.new_exception(class, Rc::clone(arg::<&Rc<String>>(&args, 0)?)))
+    });
+
+    // Makes a coroutine of a function taking at most one argument, which it is
+    // given by the first `Resume`.
+    define(vm, "silicon.lang.Coroutine.new", &["silicon.lang.Func"], "silicon.lang.Coroutine", |vm, args| {
+        let function = Rc::clone(arg::<&Rc<Function>>(&args, 0)?);
+        if let FunctionBody::Native(_) = function.body {
+            return Err(ErrorKind::UnsupportedOperand { operation: "make a coroutine of native", operand: args[0].get_canonical_name() })
+        }
+        if function.param_types.len() > 1 {
+            return Err(ErrorKind::ArityMismatch { expected: 1, found: function.param_types.len() })
+        }
+        Ok(vm.heap.alloc_coroutine(Coroutine::new(function)))
+    });
+
+    // One of "suspended", "running" or "done".
+    define(vm, "silicon.lang.Coroutine.status", &["silicon.lang.Coroutine"], "silicon.lang.String", |vm, args| {
+        let coroutine = arg::<&Rc<Coroutine>>(&args, 0)?;
+        Ok(Instance::Str(vm.string_pool.pool_str(coroutine.status())))
+    });
+
+    define(vm, "silicon.lang.Complex.new", &["silicon.lang.Float64", "silicon.lang.Float64"], "silicon.lang.Complex", |_, args| {
+        Ok(Instance::Complex(Complex64::new(arg(&args, 0)?, arg(&args, 1)?)))
+    });
+
+    define(vm, "silicon.lang.Complex.real", &["silicon.lang.Complex"], "silicon.lang.Float64", |_, args| {
+        Ok(Instance::Float64(arg::<Complex64>(&args, 0)?.re))
+    });
+
+    define(vm, "silicon.lang.Complex.imaginary", &["silicon.lang.Complex"], "silicon.lang.Float64", |_, args| {
+        Ok(Instance::Float64(arg::<Complex64>(&args, 0)?.im))
+    });
+}
+
+// The `silicon.lang.String` library. Indices and lengths count chars, that is
+// Unicode scalar values, unless their name says otherwise; `graphemes` splits a
+// string into the clusters a reader would see as single characters.
+pub(crate) fn register_strings(vm: &mut VM) {
+    define(vm, "silicon.lang.String.length", &["silicon.lang.String"], "silicon.lang.Int32", |vm, args| {
+        let length = vm.string_pool.char_count(arg::<&Rc<String>>(&args, 0)?);
+        Ok(Instance::Int32(to_int32(length)?))
+    });
+
+    define(vm, "silicon.lang.String.byteLength", &["silicon.lang.String"], "silicon.lang.Int32", |_, args| {
+        Ok(Instance::Int32(to_int32(arg::<&Rc<String>>(&args, 0)?.len())?))
+    });
+
+    // Takes the chars from `start` up to but not including `end`.
+    define(vm, "silicon.lang.String.substring", &["silicon.lang.String", "silicon.lang.Int32", "silicon.lang.Int32"], "silicon.lang.String", |vm, args| {
+        let string = arg::<&Rc<String>>(&args, 0)?;
+        let length = vm.string_pool.char_count(string);
+        let (start, end) = (arg::<i32>(&args, 1)?, arg::<i32>(&args, 2)?);
+        for index in [start, end].iter() {
+            if *index < 0 || *index as usize > length {
+                return Err(ErrorKind::IndexOutOfBounds { index: *index as i128, length })
+            }
+        }
+        if start > end {
+            return Err(ErrorKind::InvalidRange { start: start as i128, end: end as i128 })
+        }
+        let out_of_bounds = |index: i32| ErrorKind::IndexOutOfBounds { index: index as i128, length };
+        let start = vm.string_pool.byte_offset(string, start as usize).ok_or_else(|| out_of_bounds(start))?;
+        let end = vm.string_pool.byte_offset(string, end as usize).ok_or_else(|| out_of_bounds(end))?;
+        let substring = string[start..end].to_string();
+        Ok(Instance::Str(vm.string_pool.pool_string(substring)))
+    });
+
+    // Returns the char index of the first occurrence of the pattern, or -1.
+    define(vm, "silicon.lang.String.find", &["silicon.lang.String", "silicon.lang.String"], "silicon.lang.Int32", |vm, args| {
+        let string = arg::<&Rc<String>>(&args, 0)?;
+        let index = match string.find(arg::<&Rc<String>>(&args, 1)?.as_str()) {
+            Some(offset) => to_int32(vm.string_pool.char_index(string, offset))?,
+            None => -1
+        };
+        Ok(Instance::Int32(index))
+    });
+
+    define(vm, "silicon.lang.String.replace", &["silicon.lang.String", "silicon.lang.String", "silicon.lang.String"], "silicon.lang.String", |vm, args| {
+        let replaced = arg::<&Rc<String>>(&args, 0)?.replace(arg::<&Rc<String>>(&args, 1)?.as_str(), arg::<&Rc<String>>(&args, 2)?);
+        Ok(Instance::Str(vm.string_pool.pool_string(replaced)))
+    });
+
+    define(vm, "silicon.lang.String.split", &["silicon.lang.String", "silicon.lang.String"], "silicon.lang.Array<silicon.lang.String>", |vm, args| {
+        let parts: Vec<String> = arg::<&Rc<String>>(&args, 0)?.split(arg::<&Rc<String>>(&args, 1)?.as_str()).map(str::to_string).collect();
+        string_array(vm, parts)
+    });
+
+    define(vm, "silicon.lang.String.graphemes", &["silicon.lang.String"], "silicon.lang.Array<silicon.lang.String>", |vm, args| {
+        let graphemes: Vec<String> = arg::<&Rc<String>>(&args, 0)?.graphemes(true).map(str::to_string).collect();
+        string_array(vm, graphemes)
+    });
+
+    define(vm, "silicon.lang.String.trim", &["silicon.lang.String"], "silicon.lang.String", |vm, args| {
+        let trimmed = arg::<&Rc<String>>(&args, 0)?.trim().to_string();
+        Ok(Instance::Str(vm.string_pool.pool_string(trimmed)))
+    });
+
+    define(vm, "silicon.lang.String.toUpperCase", &["silicon.lang.String"], "silicon.lang.String", |vm, args| {
+        let upper = arg::<&Rc<String>>(&args, 0)?.to_uppercase();
+        Ok(Instance::Str(vm.string_pool.pool_string(upper)))
+    });
+
+    define(vm, "silicon.lang.String.toLowerCase", &["silicon.lang.String"], "silicon.lang.String", |vm, args| {
+        let lower = arg::<&Rc<String>>(&args, 0)?.to_lowercase();
+        Ok(Instance::Str(vm.string_pool.pool_string(lower)))
+    });
+
+    define(vm, "silicon.lang.String.startsWith", &["silicon.lang.String", "silicon.lang.String"], "silicon.lang.Boolean", |_, args| {
+        Ok(Instance::Bool(arg::<&Rc<String>>(&args, 0)?.starts_with(arg::<&Rc<String>>(&args, 1)?.as_str())))
+    });
+
+    define(vm, "silicon.lang.String.endsWith", &["silicon.lang.String", "silicon.lang.String"], "silicon.lang.Boolean", |_, args| {
+        Ok(Instance::Bool(arg::<&Rc<String>>(&args, 0)?.ends_with(arg::<&Rc<String>>(&args, 1)?.as_str())))
+    });
+}
+
+// Registers a native whose types are all built in, which cannot fail.
+fn define<F>(vm: &mut VM, name: &str, param_types: &[&str], return_type: &str, function: F)
+    where F: Fn(&mut VM, Vec<Instance>) -> Result<Instance, ErrorKind> + 'static
+{
+    vm.register_native(name, param_types, return_type, function).expect("core types are registered")
+}
+
+// A value that can be picked out of a native's argument of the type named by
+// `TYPE_NAME`.
+trait FromArg<'a>: Sized {
+    const TYPE_NAME: &'static str;
+
+    fn from_arg(instance: &'a Instance) -> Option<Self>;
+}
+
+macro_rules! impl_from_arg {
+    ($($t:ty, $name:literal, $variant:ident($value:ident) => $result:expr);* $(;)?) => {
+        $(
+            impl<'a> FromArg<'a> for $t {
+                const TYPE_NAME: &'static str = $name;
+
+                fn from_arg(instance: &'a Instance) -> Option<Self> {
+                    match instance {
+                        Instance::$variant($value) => Some($result),
+                        _ => None
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_from_arg!(
+    i32, "silicon.lang.Int32", Int32(value) => *value;
+    f64, "silicon.lang.Float64", Float64(value) => *value;
+    Complex64, "silicon.lang.Complex", Complex(value) => *value;
+    &'a Rc<String>, "silicon.lang.String", Str(value) => value;
+    &'a Rc<Function>, "silicon.lang.Func", Func(value) => value;
+    &'a Rc<Coroutine>, "silicon.lang.Coroutine", Coroutine(value) => value;
+);
+
+// The argument at `index`. Argument types are checked against the native's
+// parameter types before it is called, so this only fails if they disagree
+// with what the native expects.
+fn arg<'a, T: FromArg<'a>>(args: &'a [Instance], index: usize) -> Result<T, ErrorKind> {
+    let instance = args.get(index).ok_or(ErrorKind::ArityMismatch { expected: index + 1, found: args.len() })?;
+    T::from_arg(instance).ok_or_else(|| ErrorKind::TypeMismatch { expected: Rc::new(T::TYPE_NAME.to_string()), found: instance.get_canonical_name() })
+}
+
+fn to_int32(value: usize) -> Result<i32, ErrorKind> {
+    i32::try_from(value).map_err(|_| ErrorKind::IntegerOverflow { operation: "measure", type_name: Rc::new("silicon.lang.Int32".to_string()) })
+}
+
+fn string_array(vm: &mut VM, strings: Vec<String>) -> Result<Instance, ErrorKind> {
+    let string_type = vm.type_registry.get_by_name(vm.string_pool.pool_str("silicon.lang.String"))?;
+    let array_type = vm.type_registry.get_array_type(string_type)?;
+    let elements = strings.into_iter().map(|string| Instance::Str(vm.string_pool.pool_string(string))).collect();
+    Ok(vm.heap.alloc_array(elements, array_type))
+}
@@ -1,6 +1,10 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use num_traits::ToPrimitive;
 use crate::instance::Instance;
-use crate::runtime::Register;
+use crate::error::ErrorKind;
+use crate::decimal::RoundingMode;
 
 // OpCode instructions. All instructions should be 4 bytes at the most.
 #[derive(Debug)]
@@ -14,7 +18,17 @@ pub enum OpCode {
     Subtract,
     Multiply,
     Divide,
+    // Divides two decimals to the given scale, rounding with the given mode.
+    DivideRounded(u8, RoundingMode),
     Power,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    // Arithmetic for signed integers and logical for unsigned ones.
+    ShiftRight,
     IntNegate,
     LogicNegate,
     Less,
@@ -24,25 +38,191 @@ pub enum OpCode {
     Eq,
     NotEq,
     Is(u16),
+    // Converts the value on top of the stack to the given numeric or char type.
+    Convert(u16),
     Concat,
+    // Pops the given number of values and formats them with the template in the
+    // given string constant.
+    Format(u16, u8),
+    // Jumps to a label. When the bool is set, pops a boolean first and only
+    // jumps if it is false.
     Jump(bool, u16),
+    // Pops a boolean and jumps to a label if it is true.
+    JumpIfTrue(u16),
     Call,
     Return(bool),
-    InitArray(u16),
+    // Pops the given number of elements into an array of the given element type.
+    InitArray(u16, u16),
     IndexGet,
     IndexSet,
+    // Opens a scope for the given number of variables. Exiting it drops every
+    // variable declared since, so a loop whose body is a scope reuses the same
+    // register slots on each iteration. `break` and `continue` are lowered to an
+    // `ExitScope` for each scope they leave, followed by a `Jump`.
     EnterScope(u16),
     ExitScope,
+    // Pushes the native function named by a string constant.
+    GetNative(u16),
+    // Pops a class and pushes a new instance of it.
+    New,
+    // Like `New`, for a generic class parameterised as the given type.
+    NewGeneric(u16),
+    // Pops an instance and pushes the value of its field at the given index.
+    GetField(u16),
+    // Pops a value, then an instance, and stores the value in the instance's field.
+    SetField(u16),
+    // Calls the method named by a string constant on the receiver.
+    Invoke(u16),
+    // Pushes a closure of the function in the given constant; see `Function`.
+    Closure(u16),
+    // Pushes or pops into the variable the running closure captured at the given index.
+    GetCaptured(u16),
+    SetCaptured(u16),
+    // Pops a value and throws it.
+    Throw,
+    // Installs the handler at the given index in the handler table until the
+    // matching `EndTry`. Exceptions thrown in between, including from called
+    // functions, unwind the frame to where it was at the `Try` and jump to the
+    // handler's label with the exception on the stack, if it is of the
+    // handler's type. Like `ExitScope`, an `EndTry` is needed for each try
+    // block a jump leaves.
+    Try(u16),
+    EndTry,
+    // Pops an integer, char or string and jumps to the label the switch table
+    // at the given index has for it, or to the table's default label.
+    Switch(u16),
+    // Pops a value and suspends the running coroutine, which `Resume` pushes it
+    // for. When the coroutine is resumed, pushes the value it was resumed with.
+    Yield,
+    // Pops a coroutine, then a value to send it, and runs the coroutine until it
+    // yields or returns, pushing the value it yielded or returned.
+    Resume,
     // Debug only.
     Print,
 }
 
+// An entry in a chunk's handler table: the label of a catch block and, if
+// given, the type an exception must have to be caught there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handler {
+    pub label: u16,
+    pub catch_type: Option<u16>
+}
+
+// An entry in a chunk's switch table: the label `Switch` jumps to for each
+// key, and for any other value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchTable {
+    pub default: u16,
+    pub cases: SwitchCases
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwitchCases {
+    // The labels for each integer from `first` on, in order.
+    Dense { first: i128, labels: Vec<u16> },
+    Sparse(HashMap<SwitchKey, u16>)
+}
+
+// A value `Switch` can branch on. Integers are keyed by value whatever their
+// type, so a `silicon.lang.Byte` matches the same case as a `silicon.lang.Int64`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SwitchKey {
+    Int(i128),
+    Char(char),
+    Str(Rc<String>)
+}
+
+impl SwitchKey {
+    // The key for `value`, or None if it is not an integer, char or string, or
+    // is an integer too large for any key.
+    pub fn of(value: &Instance) -> Option<SwitchKey> {
+        let key = match value {
+            Instance::Byte(value) => SwitchKey::Int(*value as i128),
+            Instance::UByte(value) => SwitchKey::Int(*value as i128),
+            Instance::Int16(value) => SwitchKey::Int(*value as i128),
+            Instance::UInt16(value) => SwitchKey::Int(*value as i128),
+            Instance::Int32(value) => SwitchKey::Int(*value as i128),
+            Instance::UInt32(value) => SwitchKey::Int(*value as i128),
+            Instance::Int64(value) => SwitchKey::Int(*value as i128),
+            Instance::UInt64(value) => SwitchKey::Int(*value as i128),
+            Instance::Int128(value) => SwitchKey::Int(*value),
+            Instance::UInt128(value) => SwitchKey::Int(value.to_i128()?),
+            Instance::BigInt(value) => SwitchKey::Int(value.to_i128()?),
+            Instance::UBigInt(value) => SwitchKey::Int(value.to_i128()?),
+            Instance::Char(c) => SwitchKey::Char(*c),
+            Instance::Str(string) => SwitchKey::Str(Rc::clone(string)),
+            _ => return None
+        };
+        Some(key)
+    }
+}
+
+impl SwitchTable {
+    // Builds a dense table when every key is an integer and at least half of
+    // the integers from the lowest key to the highest have a case, and a
+    // sparse one otherwise. Cases that jump to the default label are left out,
+    // so the same cases always build the same table.
+    pub fn new(default: u16, cases: Vec<(SwitchKey, u16)>) -> SwitchTable {
+        let cases: HashMap<SwitchKey, u16> = cases.into_iter().filter(|(_, label)| *label != default).collect();
+        let integers: Vec<i128> = cases.keys().filter_map(|key| match key {
+            SwitchKey::Int(value) => Some(*value),
+            _ => None
+        }).collect();
+        let (first, last) = match (integers.iter().min(), integers.iter().max()) {
+            (Some(first), Some(last)) if integers.len() == cases.len() => (*first, *last),
+            _ => return SwitchTable { default, cases: SwitchCases::Sparse(cases) }
+        };
+
+        // The span of `i128::MIN` to `i128::MAX` is one more than a u128 holds.
+        let span = match last.abs_diff(first).checked_add(1) {
+            Some(span) if span <= u16::MAX as u128 && span <= 2 * cases.len() as u128 => span,
+            _ => return SwitchTable { default, cases: SwitchCases::Sparse(cases) }
+        };
+        let mut labels = vec![default; span as usize];
+        for (key, label) in cases {
+            if let SwitchKey::Int(value) = key {
+                labels[(value - first) as usize] = label;
+            }
+        }
+        SwitchTable { default, cases: SwitchCases::Dense { first, labels } }
+    }
+
+    pub fn target(&self, key: &SwitchKey) -> u16 {
+        let found = match (&self.cases, key) {
+            (SwitchCases::Dense { first, labels }, SwitchKey::Int(value)) => value.checked_sub(*first)
+                .and_then(|index| usize::try_from(index).ok())
+                .and_then(|index| labels.get(index)),
+            (SwitchCases::Dense { .. }, _) => None,
+            (SwitchCases::Sparse(cases), key) => cases.get(key)
+        };
+        *found.unwrap_or(&self.default)
+    }
+
+    // Every case that does not jump to the default label, ordered by key.
+    pub fn cases(&self) -> Vec<(SwitchKey, u16)> {
+        let mut cases: Vec<(SwitchKey, u16)> = match &self.cases {
+            // A table read from a file may run past `i128::MAX`, and no key
+            // can reach the labels beyond it.
+            SwitchCases::Dense { first, labels } => labels.iter().enumerate()
+                .filter_map(|(index, label)| Some((SwitchKey::Int(first.checked_add(index as i128)?), *label)))
+                .collect(),
+            SwitchCases::Sparse(cases) => cases.iter().map(|(key, label)| (key.clone(), *label)).collect()
+        };
+        cases.retain(|(_, label)| *label != self.default);
+        cases.sort();
+        cases
+    }
+}
+
 #[derive(Debug)]
 pub struct Chunk {
     pub op_codes: Vec<OpCode>,
     pub is_locked: bool,
     pub jump_table: HashMap<u16, usize>,
     pub const_table:  HashMap<u16, Instance>,
+    pub handler_table: HashMap<u16, Handler>,
+    pub switch_table: HashMap<u16, SwitchTable>,
 }
 
 impl Chunk {
@@ -51,7 +231,9 @@ impl Chunk {
             op_codes: vec![],
             is_locked: false,
             jump_table: Default::default(),
-            const_table: Default::default()
+            const_table: Default::default(),
+            handler_table: Default::default(),
+            switch_table: Default::default()
         }
     }
 
@@ -77,12 +259,71 @@ impl Chunk {
         return self.op_codes.get(pt)
     }
 
-    pub fn get_const(&self, index: u16) -> Instance {
+    pub fn get_const(&self, index: u16) -> Result<Instance, ErrorKind> {
         match self.const_table.get(&index) {
-            Some(instance) => {
-                return instance.to_owned()
-            },
-            None => panic!("Constant table slot `{}` was empty.", index)
-        };
+            Some(instance) => Ok(instance.to_owned()),
+            None => Err(ErrorKind::MissingConstant(index))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(keys: &[i128]) -> Vec<(SwitchKey, u16)> {
+        keys.iter().enumerate().map(|(index, key)| (SwitchKey::Int(*key), index as u16 + 1)).collect()
+    }
+
+    #[test]
+    fn half_full_ranges_are_dense() {
+        let table = SwitchTable::new(0, ints(&[10, 11, 13]));
+        assert_eq!(table.cases, SwitchCases::Dense { first: 10, labels: vec![1, 2, 0, 3] });
+        assert_eq!(table.target(&SwitchKey::Int(13)), 3);
+        assert_eq!(table.target(&SwitchKey::Int(12)), 0);
+        assert_eq!(table.target(&SwitchKey::Int(i128::MIN)), 0);
+        assert_eq!(table.target(&SwitchKey::Char('a')), 0);
+
+        assert!(matches!(SwitchTable::new(0, ints(&[0, 5])).cases, SwitchCases::Sparse(_)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn extreme_keys_are_sparse() {
+        let table = SwitchTable::new(0, ints(&[i128::MIN, i128::MAX]));
+        assert!(matches!(table.cases, SwitchCases::Sparse(_)));
+        assert_eq!(table.target(&SwitchKey::Int(i128::MIN)), 1);
+        assert_eq!(table.target(&SwitchKey::Int(i128::MAX)), 2);
+        assert_eq!(table.cases(), ints(&[i128::MIN, i128::MAX]));
+
+        let table = SwitchTable::new(0, ints(&[i128::MAX - 1, i128::MAX]));
+        assert_eq!(table.cases, SwitchCases::Dense { first: i128::MAX - 1, labels: vec![1, 2] });
+        assert_eq!(table.cases(), ints(&[i128::MAX - 1, i128::MAX]));
+    }
+
+    #[test]
+    fn spans_are_limited_to_u16_max() {
+        let cases = |last: i128| (0..=last).map(|key| (SwitchKey::Int(key), 1)).collect();
+        assert!(matches!(SwitchTable::new(0, cases(u16::MAX as i128 - 1)).cases, SwitchCases::Dense { .. }));
+        assert!(matches!(SwitchTable::new(0, cases(u16::MAX as i128)).cases, SwitchCases::Sparse(_)));
+    }
+
+    #[test]
+    fn cases_for_the_default_label_are_left_out() {
+        let table = SwitchTable::new(2, ints(&[1, 2, 3]));
+        assert_eq!(table.cases(), vec![(SwitchKey::Int(1), 1), (SwitchKey::Int(3), 3)]);
+        assert_eq!(table, SwitchTable::new(2, vec![(SwitchKey::Int(3), 3), (SwitchKey::Int(1), 1)]));
+    }
+
+    #[test]
+    fn mixed_keys_are_sparse() {
+        let table = SwitchTable::new(0, vec![(SwitchKey::Int(1), 1), (SwitchKey::Char('q'), 2)]);
+        assert!(matches!(table.cases, SwitchCases::Sparse(_)));
+        assert_eq!(table.target(&SwitchKey::Char('q')), 2);
+    }
+
+    #[test]
+    fn dense_labels_past_i128_max_are_unreachable() {
+        let table = SwitchTable { default: 0, cases: SwitchCases::Dense { first: i128::MAX, labels: vec![1, 2, 3] } };
+        assert_eq!(table.cases(), vec![(SwitchKey::Int(i128::MAX), 1)]);
+    }
+}
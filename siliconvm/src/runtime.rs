@@ -1,12 +1,111 @@
 use std::rc::Rc;
 use std::collections::HashMap;
 use std::slice::Chunks;
-use crate::opcode::{OpCode, Chunk};
-use crate::instance::{Instance, Instance::*, Variable, Type};
-use std::convert::TryInto;
-use crate::runtime::InstructionResult::{Return, Continue, ReturnWith, ExitScope};
+use crate::opcode::{OpCode, Chunk, SwitchKey};
+use crate::instance::{Instance, Instance::*, Variable, Type, Function, FunctionBody, NativeFunction, Class, Capture, TypeArg, parameterised_name};
+use crate::natives;
+use std::convert::{TryInto, TryFrom};
+use crate::runtime::InstructionResult::{Return, Continue, ReturnWith, Yield};
 use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::mem;
+use std::fmt::Display;
+use num_traits::Zero;
 use crate::string_pool::StringPool;
+use crate::heap::Heap;
+use crate::exception::Exceptions;
+use crate::coroutine::{Coroutine, CoroutineState, Suspension, SuspendedFrame};
+use crate::error::{RuntimeError, ErrorKind};
+use crate::math;
+use crate::math::OverflowPolicy;
+use crate::decimal::RoundingMode;
+use crate::format;
+use crate::bytecode::MAX_NESTING;
+
+// Evaluates `$body` with `$l` and `$r` bound to two integers of the same type
+// and wraps the result back into an instance of that type. `$body` is generic
+// over the integer types in `math`, and `$big` likewise over the big integer
+// types. Operands should already have been promoted to a common type.
+macro_rules! integer_operation {
+    ($left:expr, $right:expr, |$l:ident, $r:ident| $body:expr, |$bl:ident, $br:ident| $big:expr, $name:expr) => {
+        match ($left, $right) {
+            (Byte($l), Byte($r)) => Byte($body),
+            (UByte($l), UByte($r)) => UByte($body),
+            (Int16($l), Int16($r)) => Int16($body),
+            (UInt16($l), UInt16($r)) => UInt16($body),
+            (Int32($l), Int32($r)) => Int32($body),
+            (UInt32($l), UInt32($r)) => UInt32($body),
+            (Int64($l), Int64($r)) => Int64($body),
+            (UInt64($l), UInt64($r)) => UInt64($body),
+            (Int128($l), Int128($r)) => Int128($body),
+            (UInt128($l), UInt128($r)) => UInt128($body),
+            (BigInt($bl), BigInt($br)) => BigInt($big),
+            (UBigInt($bl), UBigInt($br)) => UBigInt($big),
+            (left, right) => return Err(unsupported_operands($name, &left, &right))
+        }
+    };
+}
+
+// Evaluates `$body` with `$l` and `$r` bound to two decimals of the same type,
+// which may have different scales, and wraps the result back into an instance
+// of that type.
+macro_rules! decimal_operation {
+    ($left:expr, $right:expr, |$l:ident, $r:ident| $body:expr, $name:expr) => {
+        match ($left, $right) {
+            (Decimal16($l), Decimal16($r)) => Decimal16($body),
+            (UDecimal16($l), UDecimal16($r)) => UDecimal16($body),
+            (Decimal32($l), Decimal32($r)) => Decimal32($body),
+            (UDecimal32($l), UDecimal32($r)) => UDecimal32($body),
+            (Decimal64($l), Decimal64($r)) => Decimal64($body),
+            (UDecimal64($l), UDecimal64($r)) => UDecimal64($body),
+            (Decimal128($l), Decimal128($r)) => Decimal128($body),
+            (UDecimal128($l), UDecimal128($r)) => UDecimal128($body),
+            (left, right) => return Err(unsupported_operands($name, &left, &right))
+        }
+    };
+}
+
+// Like `integer_operation`, but also evaluates `$float` with the operands bound
+// to two floats of the same type, `$decimal` as in `decimal_operation` and, if
+// given, `$complex` with the operands bound to two complex numbers.
+macro_rules! numeric_operation {
+    ($left:expr, $right:expr, |$l:ident, $r:ident| $body:expr, |$fl:ident, $fr:ident| $float:expr, |$bl:ident, $br:ident| $big:expr,
+            |$dl:ident, $dr:ident| $decimal:expr, |$cl:ident, $cr:ident| $complex:expr, $name:expr) => {
+        match ($left, $right) {
+            (Complex($cl), Complex($cr)) => Complex($complex),
+            (left, right) => numeric_operation!(left, right, |$l, $r| $body, |$fl, $fr| $float, |$bl, $br| $big, |$dl, $dr| $decimal, $name)
+        }
+    };
+    ($left:expr, $right:expr, |$l:ident, $r:ident| $body:expr, |$fl:ident, $fr:ident| $float:expr, |$bl:ident, $br:ident| $big:expr,
+            |$dl:ident, $dr:ident| $decimal:expr, $name:expr) => {
+        match ($left, $right) {
+            (Float32($fl), Float32($fr)) => Float32($float),
+            (Float64($fl), Float64($fr)) => Float64($float),
+            (left, right) if is_decimal(&left) => decimal_operation!(left, right, |$dl, $dr| $decimal, $name),
+            (left, right) => integer_operation!(left, right, |$l, $r| $body, |$bl, $br| $big, $name)
+        }
+    };
+}
+
+// Evaluates `$body` with `$v` bound to an integer of any type and wraps the
+// result back into an instance of that type.
+macro_rules! unary_integer_operation {
+    ($operand:expr, |$v:ident| $body:expr, $name:expr) => {
+        match $operand {
+            Byte($v) => Byte($body),
+            UByte($v) => UByte($body),
+            Int16($v) => Int16($body),
+            UInt16($v) => UInt16($body),
+            Int32($v) => Int32($body),
+            UInt32($v) => UInt32($body),
+            Int64($v) => Int64($body),
+            UInt64($v) => UInt64($body),
+            Int128($v) => Int128($body),
+            UInt128($v) => UInt128($body),
+            operand => return Err(unsupported_operand($name, &operand))
+        }
+    };
+}
 
 pub struct NewCallFrame {
     register_offset: u16,
@@ -32,10 +131,27 @@ pub enum NewInstructionResult {
 pub struct VM {
     pub(crate) type_registry: TypeRegistry,
     pub string_pool: StringPool,
+    pub heap: Heap,
+    pub(crate) exceptions: Exceptions,
+    natives: HashMap<Rc<String>, Rc<Function>>,
     pub register: Register,
     pub stack: Vec<Instance>,
+    // The chunks being executed and their frames, innermost last, whose
+    // constants and captured variables are roots for the heap.
+    active_frames: Vec<(Rc<Chunk>, Rc<RefCell<CallFrame>>)>,
+    // The coroutines being run by `Resume`, innermost last.
+    running: Vec<Rc<Coroutine>>,
+    // How many of `running` cannot be yielded from, because a call that has to
+    // run to completion, such as an `equals` method, was made inside them.
+    yield_floor: usize,
+    // While a `Yield` unwinds back to its `Resume`, the value it yielded and
+    // the frames unwound so far, innermost first.
+    yielded: Option<Instance>,
+    suspended_frames: Vec<SuspendedFrame>,
     pub pc : usize,
-    jumped: bool
+    jumped: bool,
+    // What integer arithmetic does when a result does not fit in its type.
+    pub overflow_policy: OverflowPolicy
 }
 
 impl VM {
@@ -43,338 +159,899 @@ impl VM {
     pub fn new() -> VM {
         let mut string_pool = StringPool::new();
         let mut type_registry = TypeRegistry::new(&mut string_pool);
-        VM {
+        let exceptions = Exceptions::new(&mut type_registry, &mut string_pool);
+        let mut vm = VM {
             type_registry,
             string_pool,
+            heap: Heap::new(),
+            exceptions,
+            natives: Default::default(),
             register: Register::new(true),
             stack: vec![],
+            active_frames: vec![],
+            running: vec![],
+            yield_floor: 0,
+            yielded: None,
+            suspended_frames: vec![],
             pc: 0,
             jumped: false,
+            overflow_policy: OverflowPolicy::Error,
+        };
+        natives::register_core(&mut vm);
+        natives::register_strings(&mut vm);
+        vm
+    }
+
+    // Makes a host function available to bytecode under its canonical name. The
+    // types are given by canonical name and must already be registered.
+    pub fn register_native<F>(&mut self, name: &str, param_types: &[&str], return_type: &str, function: F) -> Result<(), ErrorKind>
+        where F: Fn(&mut VM, Vec<Instance>) -> Result<Instance, ErrorKind> + 'static
+    {
+        let mut params = vec![];
+        for param in param_types {
+            let index = self.type_registry.resolve_name(param)?;
+            params.push(self.type_registry.get(index)?);
         }
+        let index = self.type_registry.resolve_name(return_type)?;
+        let return_type = self.type_registry.get(index)?;
+
+        let native = Function::new_native(Rc::new(function), params, return_type);
+        self.natives.insert(self.string_pool.pool_str(name), Rc::new(native));
+        Ok(())
     }
 
-    pub fn execute_chunk(&mut self, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>, args: Vec<Instance>, arg_types: Vec<Rc<Type>>) -> InstructionResult {
-        let register_offset = frame.borrow().register_access_offset;
+    pub fn execute_chunk(&mut self, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>, args: Vec<Instance>, arg_types: Vec<Rc<Type>>) -> Result<InstructionResult, RuntimeError> {
+        if args.len() != arg_types.len() {
+            return Err(RuntimeError::new(ErrorKind::ArityMismatch { expected: arg_types.len(), found: args.len() }, self.pc))
+        }
 
-        for i in 0..args.len() {
-            let _type: Rc<Type> = Rc::clone(&arg_types.get(i).unwrap());
-            let instance = args.get(i).unwrap();
-            self.register.declare(true, instance.to_owned(), _type);
+        for (instance, _type) in args.into_iter().zip(arg_types) {
+            self.register.declare(true, instance, _type).map_err(|kind| RuntimeError::new(kind, self.pc))?;
         }
 
+        self.active_frames.push((Rc::clone(&chunk), Rc::clone(&frame)));
+        let result = self.run_chunk(chunk, frame);
+        self.active_frames.pop();
+        result
+    }
+
+    fn run_chunk(&mut self, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> Result<InstructionResult, RuntimeError> {
         loop {
-            let op = chunk.get(self.pc);
-            match op {
-                Some(code) => {
-                    let result = self.execute_instruction(code, Rc::clone(&chunk), Rc::clone(&frame));
-                    match result {
-                        Continue => {},
-                        Return | ExitScope => return result,
-                        ReturnWith(instance) => return ReturnWith(instance),
-                    }
-                },
-                None => return Return
+            let result = match chunk.get(self.pc) {
+                Some(code) => self.execute_instruction(code, Rc::clone(&chunk), Rc::clone(&frame)),
+                None => return Ok(Return)
+            };
+            if let Some(result) = self.finish_instruction(result, &chunk, &frame)? {
+                return Ok(result)
+            }
+        }
+    }
+
+    // Moves on from the instruction at `self.pc` given its result, returning
+    // what the frame returned or yielded if the instruction ended it.
+    fn finish_instruction(&mut self, result: Result<InstructionResult, ErrorKind>, chunk: &Rc<Chunk>, frame: &Rc<RefCell<CallFrame>>) -> Result<Option<InstructionResult>, RuntimeError> {
+        match result {
+            Ok(Continue) => {},
+            Ok(result) => return Ok(Some(result)),
+            Err(kind) => return match self.catch(kind, chunk, frame) {
+                Ok(()) => Ok(None),
+                Err(kind) => Err(RuntimeError::new(kind, self.pc))
             }
-            if !self.jumped {self.pc += 1}
-            self.jumped = false
         }
+        if self.yielded.is_some() {
+            self.suspended_frames.push(SuspendedFrame { chunk: Rc::clone(chunk), frame: Rc::clone(frame), pc: self.pc, call: None });
+            return Ok(Some(Yield))
+        }
+        if !self.jumped {self.pc += 1}
+        self.jumped = false;
+
+        // Between instructions every live value is on the stack, in the
+        // register or in a constant table, so the heap can be traced.
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        Ok(None)
+    }
+
+    // Frees the arrays and instances that can no longer be reached, including
+    // cycles, and returns how many there were.
+    pub fn collect_garbage(&mut self) -> usize {
+        let mut variables: Vec<Instance> = self.register.internal.values().map(|variable| variable.borrow().stored.to_owned()).collect();
+        for (_, frame) in &self.active_frames {
+            variables.extend(frame.borrow().upvalues.iter().map(|variable| variable.borrow().stored.to_owned()));
+        }
+        variables.extend(self.running.iter().map(|coroutine| Instance::Coroutine(Rc::clone(coroutine))));
+        let constants = self.active_frames.iter().flat_map(|(chunk, _)| chunk.const_table.values());
+        self.heap.collect(self.stack.iter().chain(variables.iter()).chain(constants))
     }
 
-    pub fn execute_instruction(&mut self, op_code: &OpCode, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> InstructionResult {
+    pub fn execute_instruction(&mut self, op_code: &OpCode, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> Result<InstructionResult, ErrorKind> {
         match op_code {
             OpCode::GetTrue => self.stack.push(Bool(true)),
             OpCode::GetFalse => self.stack.push(Bool(false)),
-            OpCode::Get(get_const, index) => self.get_variable(*index, *get_const, chunk, frame),
-            OpCode::Declare(is_const, type_index) => self.declare_variable(*type_index, *is_const, frame),
-            OpCode::Set(index) => self.set_variable(*index, chunk, frame),
-            OpCode::Add => self.add_operands(frame.borrow().stack_offset),
-            OpCode::Subtract => self.subtract_operands(frame.borrow().stack_offset),
-            OpCode::Multiply => self.multiply_operands(frame.borrow().stack_offset),
-            OpCode::Divide => self.divide_operands(frame.borrow().stack_offset),
-            OpCode::Power => self.pow_operands(frame.borrow().stack_offset),
-            OpCode::IntNegate => self.negate_operand(frame.borrow().stack_offset),
-            OpCode::LogicNegate => self.logic_negate_operand(frame.borrow().stack_offset),
-            OpCode::Less => self.compare_operand_size(false, false, frame.borrow().stack_offset),
-            OpCode::LessOrEq => self.compare_operand_size(false, true, frame.borrow().stack_offset),
-            OpCode::Greater => self.compare_operand_size(true, false, frame.borrow().stack_offset),
-            OpCode::GreaterOrEq => self.compare_operand_size(true, true, frame.borrow().stack_offset),
-            OpCode::Eq => self.equate_operands(false, frame.borrow().stack_offset),
-            OpCode::NotEq => self.equate_operands(true, frame.borrow().stack_offset),
-            OpCode::Is(type_index) => self.type_test(*type_index, frame.borrow().stack_offset),
-            OpCode::Concat => self.concat(frame.borrow().stack_offset),
-            OpCode::Jump(value, index) => if !value {self.jump(*index, chunk); self.jumped = true} else if self.try_jump(*index, chunk, frame.borrow().stack_offset) {self.jumped = true},
-            OpCode::Call => {},
-            OpCode::Return(return_instance) => if *return_instance { return ReturnWith(self.get_stack_top(frame.borrow().stack_offset)) } else { return Return }
-            OpCode::InitArray(size) => self.make_array(*size, frame.borrow().stack_offset),
-            OpCode::IndexGet => self.index_get(frame.borrow().stack_offset),
-            OpCode::IndexSet => self.index_set(frame.borrow().stack_offset),
+            OpCode::Get(get_const, index) => self.get_variable(*index, *get_const, chunk, frame)?,
+            OpCode::Declare(is_const, type_index) => self.declare_variable(*type_index, *is_const, frame)?,
+            OpCode::Set(index) => self.set_variable(*index, chunk, frame)?,
+            OpCode::Add => self.add_operands(frame.borrow().stack_offset)?,
+            OpCode::Subtract => self.subtract_operands(frame.borrow().stack_offset)?,
+            OpCode::Multiply => self.multiply_operands(frame.borrow().stack_offset)?,
+            OpCode::Divide => self.divide_operands(frame.borrow().stack_offset)?,
+            OpCode::DivideRounded(scale, mode) => self.divide_rounded_operands(*scale, *mode, frame.borrow().stack_offset)?,
+            OpCode::Power => self.pow_operands(frame.borrow().stack_offset)?,
+            OpCode::Modulo => self.remainder_operands(frame.borrow().stack_offset)?,
+            OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor => self.bitwise_operands(op_code, frame.borrow().stack_offset)?,
+            OpCode::BitNot => self.bit_not_operand(frame.borrow().stack_offset)?,
+            OpCode::ShiftLeft => self.shift_operands(true, frame.borrow().stack_offset)?,
+            OpCode::ShiftRight => self.shift_operands(false, frame.borrow().stack_offset)?,
+            OpCode::IntNegate => self.negate_operand(frame.borrow().stack_offset)?,
+            OpCode::LogicNegate => self.logic_negate_operand(frame.borrow().stack_offset)?,
+            OpCode::Less => self.compare_operand_size(false, false, frame.borrow().stack_offset)?,
+            OpCode::LessOrEq => self.compare_operand_size(false, true, frame.borrow().stack_offset)?,
+            OpCode::Greater => self.compare_operand_size(true, false, frame.borrow().stack_offset)?,
+            OpCode::GreaterOrEq => self.compare_operand_size(true, true, frame.borrow().stack_offset)?,
+            OpCode::Eq => self.equate_operands(false, frame.borrow().stack_offset)?,
+            OpCode::NotEq => self.equate_operands(true, frame.borrow().stack_offset)?,
+            OpCode::Is(type_index) => self.type_test(*type_index, frame.borrow().stack_offset)?,
+            OpCode::Convert(type_index) => self.convert_operand(*type_index, frame.borrow().stack_offset)?,
+            OpCode::Concat => self.concat(frame.borrow().stack_offset)?,
+            OpCode::Format(index, count) => self.format_operands(*index, *count, chunk, frame.borrow().stack_offset)?,
+            OpCode::Jump(value, index) => if !value {self.jump(*index, chunk)?; self.jumped = true} else if self.try_jump(false, *index, chunk, frame.borrow().stack_offset)? {self.jumped = true},
+            OpCode::JumpIfTrue(index) => if self.try_jump(true, *index, chunk, frame.borrow().stack_offset)? {self.jumped = true},
+            OpCode::Switch(index) => {self.switch(*index, chunk, frame.borrow().stack_offset)?; self.jumped = true},
+            OpCode::Call => self.call(frame.borrow().stack_offset)?,
+            OpCode::Return(return_instance) => if *return_instance { return Ok(ReturnWith(self.get_stack_top(frame.borrow().stack_offset)?)) } else { return Ok(Return) }
+            OpCode::InitArray(size, type_index) => self.make_array(*size, *type_index, frame.borrow().stack_offset)?,
+            OpCode::IndexGet => self.index_get(frame.borrow().stack_offset)?,
+            OpCode::IndexSet => self.index_set(frame.borrow().stack_offset)?,
             OpCode::EnterScope(size) => self.enter_scope(*size, frame),
-            OpCode::ExitScope => self.exit_scope(frame),
-            OpCode::Print => println!("{}", self.get_stack_top(frame.borrow().stack_offset)),
+            OpCode::ExitScope => self.exit_scope(frame)?,
+            OpCode::Print => println!("{}", self.get_stack_top(frame.borrow().stack_offset)?),
+            OpCode::GetNative(index) => self.get_native(*index, chunk)?,
+            OpCode::New => self.new_instance(None, frame.borrow().stack_offset)?,
+            OpCode::NewGeneric(type_index) => self.new_instance(Some(*type_index), frame.borrow().stack_offset)?,
+            OpCode::GetField(index) => self.get_field(*index, frame.borrow().stack_offset)?,
+            OpCode::SetField(index) => self.set_field(*index, frame.borrow().stack_offset)?,
+            OpCode::Invoke(index) => self.invoke(*index, chunk, frame.borrow().stack_offset)?,
+            OpCode::Closure(index) => self.make_closure(*index, chunk, frame)?,
+            OpCode::GetCaptured(index) => self.get_captured(*index, frame)?,
+            OpCode::SetCaptured(index) => self.set_captured(*index, frame)?,
+            OpCode::Throw => return Err(ErrorKind::Thrown(self.get_stack_top(frame.borrow().stack_offset)?)),
+            OpCode::Try(index) => self.enter_try(*index, chunk, frame)?,
+            OpCode::EndTry => if frame.borrow_mut().handlers.pop().is_none() { return Err(ErrorKind::HandlerUnderflow) },
+            OpCode::Yield => self.yield_value(frame.borrow().stack_offset)?,
+            OpCode::Resume => self.resume(frame.borrow().stack_offset)?,
         };
-        return Continue
+        Ok(Continue)
     }
 
-    fn get_variable(&mut self, index: u16, get_const: bool, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) {
+    fn get_variable(&mut self, index: u16, get_const: bool, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
         let instance = if get_const {
-            chunk.get_const(index)
+            chunk.get_const(index)?
         } else {
-            self.register.get((&index + &frame.borrow().register_access_offset))
+            match index.checked_add(frame.borrow().register_access_offset) {
+                Some(slot) => self.register.get(slot)?,
+                None => return Err(ErrorKind::UndefinedVariable(index))
+            }
         };
         self.stack.push(instance);
+        Ok(())
     }
 
-    fn declare_variable(&mut self, type_index: u16, is_const: bool, frame: Rc<RefCell<CallFrame>>) {
-        let register = frame.borrow().register_declare_offset;
-        let instance = self.get_stack_top(frame.borrow().stack_offset);
-        let _type: Rc<Type> = self.type_registry.get(type_index);
-        self.register.declare(is_const, instance, Rc::clone(&_type))
+    fn declare_variable(&mut self, type_index: u16, is_const: bool, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        let instance = self.get_stack_top(frame.borrow().stack_offset)?;
+        let _type: Rc<Type> = self.type_registry.get(type_index)?;
+        self.register.declare(is_const, instance, _type)
     }
 
-    fn set_variable(&mut self, index: u16, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) {
-        let register_offset = frame.borrow().register_access_offset;
-        let instance = self.get_stack_top(frame.borrow().stack_offset);
-        self.register.set(index, instance)
+    fn set_variable(&mut self, index: u16, _chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        let instance = self.get_stack_top(frame.borrow().stack_offset)?;
+        match index.checked_add(frame.borrow().register_access_offset) {
+            Some(slot) => self.register.set(slot, instance),
+            None => Err(ErrorKind::UndefinedVariable(index))
+        }
     }
 
-    fn add_operands(&mut self, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
+    // Calls the function on top of the stack with the arguments beneath it, then
+    // pushes its return value, or `void` if it returned without one.
+    fn call(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let callee = self.get_stack_top(stack_offset)?;
+        let function = match callee {
+            Func(function) => function,
+            _ => return Err(unsupported_operand("call", &callee))
+        };
+        let args = self.split_stack(function.param_types.len(), stack_offset)?;
+        self.call_function(function, args)
+    }
+
+    // Looks up a method by name in the class of the receiver on top of the
+    // stack and calls it, passing the receiver as the first argument.
+    fn invoke(&mut self, name_index: u16, chunk: Rc<Chunk>, stack_offset: usize) -> Result<(), ErrorKind> {
+        let name = match chunk.get_const(name_index)? {
+            Str(name) => name,
+            constant => return Err(ErrorKind::TypeMismatch { expected: Rc::new("silicon.lang.String".to_string()), found: constant.get_canonical_name() })
+        };
+        let receiver = self.get_stack_top(stack_offset)?;
+        let method = match &receiver {
+            ClassInstance(instance) => instance.class.get_method(&name),
+            _ => return Err(unsupported_operand("invoke methods on", &receiver))
+        };
+        let method = match method {
+            Some(method) => method,
+            None => return Err(ErrorKind::UndefinedMethod { class: receiver.get_canonical_name(), method: name })
+        };
+
+        let arg_count = method.param_types.len().saturating_sub(1);
+        let mut args = self.split_stack(arg_count, stack_offset)?;
+        args.insert(0, receiver);
+        self.call_function(method, args)
+    }
 
-        match (left, right) {
-            (Byte(left_num), Byte(right_num)) => self.stack.push(Byte(left_num + right_num)),
-            (UByte(left_num), UByte(right_num)) => self.stack.push(UByte(left_num + right_num)),
-            (Int16(left_num), Int16(right_num)) => self.stack.push(Int16(left_num + right_num)),
-            (UInt16(left_num), UInt16(right_num)) => self.stack.push(UInt16(left_num + right_num)),
-            _ => panic!("The operands cannot be added!")
+    fn call_function(&mut self, function: Rc<Function>, args: Vec<Instance>) -> Result<(), ErrorKind> {
+        if args.len() != function.param_types.len() {
+            return Err(ErrorKind::ArityMismatch { expected: function.param_types.len(), found: args.len() })
         }
+
+        let chunk = match &function.body {
+            FunctionBody::Bytecode(chunk) => Rc::clone(chunk),
+            FunctionBody::Native(native) => return self.call_native(&function, Rc::clone(native), args)
+        };
+
+        let register_offset = self.register.size;
+        let stack_size = self.stack.len();
+        let return_pc = self.pc;
+        let result = self.run_function(&function, chunk, args);
+        self.pc = return_pc;
+        self.finish_call(&function, register_offset, stack_size, result)
+    }
+
+    // Runs `chunk`, the body of `function`, in a new frame on top of the current one.
+    fn run_function(&mut self, function: &Function, chunk: Rc<Chunk>, args: Vec<Instance>) -> Result<InstructionResult, RuntimeError> {
+        let mut frame = CallFrame::new_with_offset(self.register.size, self.register.size, self.stack.len());
+        frame.upvalues = function.upvalues.borrow().clone();
+        self.pc = 0;
+        self.execute_chunk(chunk, Rc::new(RefCell::new(frame)), args, function.param_types.clone())
     }
 
-    fn subtract_operands(&mut self, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
+    // Pushes the return value of a call to `function` whose frame started at
+    // `register_offset` and `stack_size`, once the frame has finished.
+    fn finish_call(&mut self, function: &Rc<Function>, register_offset: u16, stack_size: usize, result: Result<InstructionResult, RuntimeError>) -> Result<(), ErrorKind> {
+        self.jumped = false;
+        if let Ok(Yield) = result {
+            // The callee's variables and operands stay where they are, for the
+            // coroutine to take with it, and the call finishes when it resumes.
+            if let Some(callee) = self.suspended_frames.last_mut() {
+                callee.call = Some(Rc::clone(function));
+            }
+            return Ok(())
+        }
+
+        // Drop whatever the callee left behind in the register and on the stack.
+        self.register.truncate(register_offset);
+        let return_value = match result {
+            Ok(ReturnWith(instance)) => instance,
+            Ok(_) => Void,
+            Err(error) => return Err(ErrorKind::InFunction(Box::new(error)))
+        };
+        self.stack.truncate(stack_size);
 
-        match (left, right) {
-            (Byte(left_num), Byte(right_num)) => self.stack.push(Byte(left_num - right_num)),
-            (UByte(left_num), UByte(right_num)) => self.stack.push(UByte(left_num - right_num)),
-            (Int16(left_num), Int16(right_num)) => self.stack.push(Int16(left_num - right_num)),
-            (UInt16(left_num), UInt16(right_num)) => self.stack.push(UInt16(left_num - right_num)),
-            _ => panic!("The operands cannot be added!")
+        if !function.return_type.is(&return_value) {
+            return Err(ErrorKind::TypeMismatch { expected: function.return_type.get_canonical_name(), found: return_value.get_canonical_name() })
         }
+        self.stack.push(return_value);
+        Ok(())
     }
 
-    fn multiply_operands(&mut self, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
+    fn call_native(&mut self, function: &Function, native: NativeFunction, args: Vec<Instance>) -> Result<(), ErrorKind> {
+        for (arg, _type) in args.iter().zip(&function.param_types) {
+            if !_type.is(arg) {
+                return Err(ErrorKind::TypeMismatch { expected: _type.get_canonical_name(), found: arg.get_canonical_name() })
+            }
+        }
 
-        match (left, right) {
-            (Byte(left_num), Byte(right_num)) => self.stack.push(Byte(left_num * right_num)),
-            (UByte(left_num), UByte(right_num)) => self.stack.push(UByte(left_num * right_num)),
-            (Int16(left_num), Int16(right_num)) => self.stack.push(Int16(left_num * right_num)),
-            (UInt16(left_num), UInt16(right_num)) => self.stack.push(UInt16(left_num * right_num)),
-            _ => panic!("The operands cannot be added!")
+        let return_value = native(self, args)?;
+        if !function.return_type.is(&return_value) {
+            return Err(ErrorKind::TypeMismatch { expected: function.return_type.get_canonical_name(), found: return_value.get_canonical_name() })
         }
+        self.stack.push(return_value);
+        Ok(())
     }
 
-    fn divide_operands(&mut self, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
+    // Instantiates the class on top of the stack. Generic classes are given the
+    // reified type at `type_index`, which has to parameterise the class's type.
+    fn new_instance(&mut self, type_index: Option<u16>, stack_offset: usize) -> Result<(), ErrorKind> {
+        let class = match self.get_stack_top(stack_offset)? {
+            Class(class) => class,
+            other => return Err(unsupported_operand("instantiate", &other))
+        };
+        if class.is_interface {
+            return Err(ErrorKind::UnsupportedOperand { operation: "instantiate interface", operand: class._type.get_canonical_name() })
+        }
+
+        let _type = match type_index {
+            Some(type_index) => {
+                let _type = self.type_registry.get(type_index)?;
+                if _type.canonical_name != class._type.canonical_name || _type.get_type_args().is_empty() {
+                    return Err(ErrorKind::TypeMismatch { expected: class._type.get_canonical_name(), found: _type.get_canonical_name() })
+                }
+                _type
+            },
+            None => Rc::clone(&class._type)
+        };
+        let instance = self.heap.alloc_instance(crate::instance::ClassInstance::new(class, _type));
+        self.stack.push(instance);
+        Ok(())
+    }
 
-        match (left, right) {
-            (Byte(left_num), Byte(right_num)) => self.stack.push(Byte(left_num / right_num)),
-            (UByte(left_num), UByte(right_num)) => self.stack.push(UByte(left_num / right_num)),
-            (Int16(left_num), Int16(right_num)) => self.stack.push(Int16(left_num / right_num)),
-            (UInt16(left_num), UInt16(right_num)) => self.stack.push(UInt16(left_num / right_num)),
-            _ => panic!("The operands cannot be added!")
+    fn get_field(&mut self, index: u16, stack_offset: usize) -> Result<(), ErrorKind> {
+        let instance = self.get_stack_top(stack_offset)?;
+        match instance {
+            ClassInstance(instance) => self.stack.push(instance.get_field(index)?),
+            _ => return Err(unsupported_operand("get fields of", &instance))
         }
+        Ok(())
     }
 
-    fn pow_operands(&mut self, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
+    fn set_field(&mut self, index: u16, stack_offset: usize) -> Result<(), ErrorKind> {
+        let value = self.get_stack_top(stack_offset)?;
+        let instance = self.get_stack_top(stack_offset)?;
+        match instance {
+            ClassInstance(instance) => instance.set_field(index, value),
+            _ => Err(unsupported_operand("set fields of", &instance))
+        }
+    }
 
-        match (left, right) {
-            (Byte(left_num), Byte(right_num)) => self.stack.push(Byte(left_num.pow(right_num.try_into().unwrap()))),
-            (UByte(left_num), UByte(right_num)) => self.stack.push(UByte(left_num.pow(right_num.try_into().unwrap()))),
-            (Int16(left_num), Int16(right_num)) => self.stack.push(Int16(left_num.pow(right_num.try_into().unwrap()))),
-            (UInt16(left_num), UInt16(right_num)) => self.stack.push(UInt16(left_num.pow(right_num.try_into().unwrap()))),
-            _ => panic!("The operands cannot be added!")
+    // Creates a closure of the function constant at `index`, capturing the
+    // variables it lists from this frame. Captured variables are shared rather
+    // than copied, so the closure and the frame see each other's updates, and
+    // the closure keeps them after their scope is exited.
+    fn make_closure(&mut self, index: u16, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        let function = match chunk.get_const(index)? {
+            Func(function) => function,
+            constant => return Err(ErrorKind::TypeMismatch { expected: Rc::new("silicon.lang.Func".to_string()), found: constant.get_canonical_name() })
+        };
+        let frame = frame.borrow();
+        let mut upvalues = vec![];
+        for capture in &function.captures {
+            let variable = match *capture {
+                Capture::Local(slot) => match slot.checked_add(frame.register_access_offset) {
+                    Some(register_slot) => self.register.capture(register_slot).ok_or(ErrorKind::UndefinedVariable(slot))?,
+                    None => return Err(ErrorKind::UndefinedVariable(slot))
+                },
+                Capture::Captured(captured) => match frame.upvalues.get(captured as usize) {
+                    Some(variable) => Rc::clone(variable),
+                    None => return Err(ErrorKind::UndefinedCapture(captured))
+                }
+            };
+            upvalues.push(variable);
         }
+        let closure = self.heap.alloc_closure(function.close_over(upvalues));
+        self.stack.push(closure);
+        Ok(())
     }
 
-    fn negate_operand(&mut self, stack_offset: usize) {
-        let operand = self.get_stack_top(stack_offset);
-        match operand {
-            Byte(num) => self.stack.push(Byte(-num)),
-            Int16(num) => self.stack.push(Int16(-num)),
-            _ => panic!("The operand cannot be negated!")
+    fn get_captured(&mut self, index: u16, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        let value = match frame.borrow().upvalues.get(index as usize) {
+            Some(variable) => variable.borrow().stored.to_owned(),
+            None => return Err(ErrorKind::UndefinedCapture(index))
+        };
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn set_captured(&mut self, index: u16, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        let value = self.get_stack_top(frame.borrow().stack_offset)?;
+        match frame.borrow().upvalues.get(index as usize) {
+            Some(variable) => variable.borrow_mut().set(value),
+            None => Err(ErrorKind::UndefinedCapture(index))
         }
     }
 
-    fn logic_negate_operand(&mut self, stack_offset: usize) {
-        let operand = self.get_stack_top(stack_offset);
-        match operand {
-            Bool(value) => self.stack.push(Bool(!value)),
-            _ => panic!("The operand cannot be negated!")
+    fn enter_try(&mut self, index: u16, chunk: Rc<Chunk>, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        let handler = match chunk.handler_table.get(&index) {
+            Some(handler) => *handler,
+            None => return Err(ErrorKind::UndefinedHandler(index))
+        };
+        let catch_type = match handler.catch_type {
+            Some(type_index) => Some(self.type_registry.get(type_index)?),
+            None => None
+        };
+        let mut frame = frame.borrow_mut();
+        let scope_depth = frame.scope_allocations.len();
+        frame.handlers.push(ActiveHandler { label: handler.label, catch_type, scope_depth, register_size: self.register.size, stack_size: self.stack.len() });
+        Ok(())
+    }
+
+    // Looks for a handler in `frame` that catches the error, unwinding the frame
+    // to where it was when the handler's `Try` ran and jumping to the catch
+    // block with the exception on the stack. Handlers that do not catch it are
+    // dropped, since the error leaves their try blocks. Errors nothing in the
+    // frame catches are returned, for the caller's frame to try next.
+    fn catch(&mut self, kind: ErrorKind, chunk: &Rc<Chunk>, frame: &Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
+        if frame.borrow().handlers.is_empty() {
+            return Err(kind)
+        }
+        let exception = match self.exception_for(&kind) {
+            Some(exception) => exception,
+            None => return Err(kind)
+        };
+        loop {
+            let handler = match frame.borrow_mut().handlers.pop() {
+                Some(handler) => handler,
+                None => return Err(kind)
+            };
+            if !handler.catch_type.as_ref().is_none_or(|_type| _type.is(&exception)) {
+                continue
+            }
+            while frame.borrow().scope_allocations.len() > handler.scope_depth {
+                self.exit_scope(Rc::clone(frame))?;
+            }
+            self.register.truncate(handler.register_size);
+            self.stack.truncate(handler.stack_size);
+            self.stack.push(exception);
+            self.jump(handler.label, Rc::clone(chunk))?;
+            self.jumped = false;
+            return Ok(())
         }
     }
 
-    fn compare_operand_size(&mut self, flip_operator: bool, equal: bool, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
-        match (left, right) {
-            (Byte(left_num), Byte(right_num)) => {
-                let mut cond = left_num < right_num;
-                if flip_operator {cond = !cond}
-                if equal {cond = cond || (left_num == right_num)}
-                self.stack.push(Bool(cond))
-            },
-            (UByte(left_num), UByte(right_num)) => {
-                let mut cond = left_num < right_num;
-                if flip_operator {cond = !cond}
-                if equal {cond = cond || (left_num == right_num)}
-                self.stack.push(Bool(cond))
-            },
-            (Int16(left_num), Int16(right_num)) => {
-                let mut cond = left_num < right_num;
-                if flip_operator {cond = !cond}
-                if equal {cond = cond || (left_num == right_num)}
-                self.stack.push(Bool(cond))
+    // The value an error is caught as, or None if it cannot be caught.
+    fn exception_for(&mut self, kind: &ErrorKind) -> Option<Instance> {
+        match kind {
+            ErrorKind::Thrown(value) => Some(value.clone()),
+            ErrorKind::InFunction(error) => self.exception_for(&error.kind),
+            kind => {
+                let class = Rc::clone(self.exceptions.class_for(kind)?);
+                let message = self.string_pool.pool_string(kind.to_string());
+                Some(self.new_exception(class, message))
+            }
+        }
+    }
+
+    // An instance of an exception class holding `message`.
+    pub(crate) fn new_exception(&mut self, class: Rc<Class>, message: Rc<String>) -> Instance {
+        let _type = Rc::clone(&class._type);
+        let exception = crate::instance::ClassInstance::new(class, _type);
+        exception.fields.borrow_mut()[0] = Str(message);
+        self.heap.alloc_instance(exception)
+    }
+
+    fn yield_value(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        if self.running.len() <= self.yield_floor {
+            return Err(ErrorKind::YieldOutsideCoroutine)
+        }
+        self.yielded = Some(self.get_stack_top(stack_offset)?);
+        Ok(())
+    }
+
+    // Pops a coroutine and the value to resume it with and runs it until it
+    // yields or returns, then pushes the value it yielded or returned.
+    fn resume(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let target = self.get_stack_top(stack_offset)?;
+        let coroutine = match target {
+            Instance::Coroutine(coroutine) => coroutine,
+            _ => return Err(unsupported_operand("resume", &target))
+        };
+        let value = self.get_stack_top(stack_offset)?;
+        let state = match coroutine.state.replace(CoroutineState::Running) {
+            CoroutineState::Running => return Err(ErrorKind::CoroutineRunning),
+            CoroutineState::Done => {
+                coroutine.state.replace(CoroutineState::Done);
+                return Err(ErrorKind::CoroutineFinished)
             },
-            (UInt16(left_num), UInt16(right_num)) => {
-                let mut cond = left_num < right_num;
-                if flip_operator {cond = !cond}
-                if equal {cond = cond || (left_num == right_num)}
-                self.stack.push(Bool(cond))
+            state => state
+        };
+
+        let register_base = self.register.size;
+        let stack_base = self.stack.len();
+        let return_pc = self.pc;
+        self.running.push(Rc::clone(&coroutine));
+        let result = match state {
+            CoroutineState::Suspended(suspension) => self.continue_coroutine(suspension, value),
+            _ => match &coroutine.function.body {
+                FunctionBody::Bytecode(chunk) => {
+                    let args = if coroutine.function.param_types.is_empty() { vec![] } else { vec![value] };
+                    self.run_function(&coroutine.function, Rc::clone(chunk), args)
+                },
+                FunctionBody::Native(_) => unreachable!("coroutines are only made of bytecode functions")
+            }
+        };
+        self.running.pop();
+        self.pc = return_pc;
+
+        if let Ok(Yield) = result {
+            let mut frames = mem::take(&mut self.suspended_frames);
+            frames.reverse();
+            let variables = self.register.split_off(register_base);
+            let operands = self.stack.split_off(stack_base);
+            coroutine.state.replace(CoroutineState::Suspended(Suspension { frames, variables, operands, register_base, stack_base }));
+            self.jumped = false;
+            self.stack.push(self.yielded.take().unwrap_or(Void));
+            return Ok(())
+        }
+        coroutine.state.replace(CoroutineState::Done);
+        self.finish_call(&coroutine.function, register_base, stack_base, result)
+    }
+
+    // Puts a suspended coroutine back on top of the register and the stack and
+    // carries on from its `Yield`, which evaluates to `value`.
+    fn continue_coroutine(&mut self, suspension: Suspension, value: Instance) -> Result<InstructionResult, RuntimeError> {
+        let register_base = self.register.size;
+        let stack_base = self.stack.len();
+        self.register.extend(suspension.variables).map_err(|kind| RuntimeError::new(kind, self.pc))?;
+        self.stack.extend(suspension.operands);
+        self.stack.push(value);
+        for suspended in &suspension.frames {
+            suspended.frame.borrow_mut().rebase(suspension.register_base, register_base, suspension.stack_base, stack_base);
+        }
+        self.resume_frames(&suspension.frames)
+    }
+
+    // Re-enters the frames of a suspended coroutine, outermost first. A frame
+    // that was calling the next one finishes the call once that frame returns.
+    fn resume_frames(&mut self, frames: &[SuspendedFrame]) -> Result<InstructionResult, RuntimeError> {
+        let (suspended, inner) = match frames.split_first() {
+            Some(split) => split,
+            None => return Ok(Return)
+        };
+        let (chunk, frame) = (Rc::clone(&suspended.chunk), Rc::clone(&suspended.frame));
+        self.active_frames.push((Rc::clone(&chunk), Rc::clone(&frame)));
+        let outcome = match inner.first() {
+            Some(callee) => {
+                let function = callee.call.clone().expect("every frame after the first was called by the one before");
+                let (register_offset, stack_size) = {
+                    let callee_frame = callee.frame.borrow();
+                    (callee_frame.register_access_offset, callee_frame.stack_offset)
+                };
+                let result = self.resume_frames(inner);
+                self.pc = suspended.pc;
+                self.finish_call(&function, register_offset, stack_size, result)
             },
-            _ => panic!("Cannot compare the size of the operands!")
+            None => {
+                self.pc = suspended.pc;
+                Ok(())
+            }
+        };
+        let result = match self.finish_instruction(outcome.map(|_| Continue), &chunk, &frame) {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => self.run_chunk(Rc::clone(&chunk), Rc::clone(&frame)),
+            Err(error) => Err(error)
+        };
+        self.active_frames.pop();
+        result
+    }
+
+    fn get_native(&mut self, index: u16, chunk: Rc<Chunk>) -> Result<(), ErrorKind> {
+        let name = match chunk.get_const(index)? {
+            Str(name) => name,
+            constant => return Err(ErrorKind::TypeMismatch { expected: Rc::new("silicon.lang.String".to_string()), found: constant.get_canonical_name() })
+        };
+        match self.natives.get(&name) {
+            Some(native) => self.stack.push(Func(Rc::clone(native))),
+            None => return Err(ErrorKind::UnknownNative(name))
+        }
+        Ok(())
+    }
+
+    fn add_operands(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = numeric_operation!(left, right, |l, r| math::add(l, r, self.overflow_policy)?, |l, r| l + r, |l, r| l + r, |l, r| l.add(r, self.overflow_policy)?, |l, r| l + r, "add");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn subtract_operands(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = numeric_operation!(left, right, |l, r| math::subtract(l, r, self.overflow_policy)?, |l, r| l - r, |l, r| math::big_subtract(l, r, self.overflow_policy)?,
+            |l, r| l.subtract(r, self.overflow_policy)?, |l, r| l - r, "subtract");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn multiply_operands(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = numeric_operation!(left, right, |l, r| math::multiply(l, r, self.overflow_policy)?, |l, r| l * r, |l, r| l * r, |l, r| l.multiply(r, self.overflow_policy)?, |l, r| l * r, "multiply");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn divide_operands(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = numeric_operation!(left, right, |l, r| math::divide(l, r, self.overflow_policy)?, |l, r| l / r, |l, r| math::big_divide(l, r)?,
+            |l, r| l.divide(r, self.overflow_policy)?, |l, r| l / r, "divide");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    // Divides two decimals to exactly `scale` digits after the point.
+    fn divide_rounded_operands(&mut self, scale: u8, mode: RoundingMode, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = decimal_operation!(left, right, |l, r| l.divide_to(r, scale, mode, self.overflow_policy)?, "divide");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn pow_operands(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = numeric_operation!(left, right, |l, r| math::power(l, exponent(r)?, self.overflow_policy)?, |l, r| l.powf(r), |l, r| l.pow(exponent(r)?),
+            |l, r| l.power(r, self.overflow_policy)?, |l, r| math::complex_power(l, r), "raise");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn remainder_operands(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = numeric_operation!(left, right, |l, r| math::remainder(l, r)?, |l, r| l % r, |l, r| math::big_remainder(l, r)?,
+            |l, r| l.remainder(r, self.overflow_policy)?, "take the remainder of");
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn bitwise_operands(&mut self, op_code: &OpCode, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let (left, right) = math::promote(left, right)?;
+        let result = match op_code {
+            OpCode::BitAnd => integer_operation!(left, right, |l, r| l & r, |l, r| l & r, "and"),
+            OpCode::BitOr => integer_operation!(left, right, |l, r| l | r, |l, r| l | r, "or"),
+            _ => integer_operation!(left, right, |l, r| l ^ r, |l, r| l ^ r, "xor")
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn bit_not_operand(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let operand = self.get_stack_top(stack_offset)?;
+        let result = match operand {
+            BigInt(value) => BigInt(!value),
+            operand => unary_integer_operation!(operand, |v| !v, "invert the bits of")
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    // Shifts the left operand by the right one, which can be an integer of any
+    // type. The result has the type of the left operand.
+    fn shift_operands(&mut self, left_shift: bool, stack_offset: usize) -> Result<(), ErrorKind> {
+        let amount = math::to_shift_amount(&self.get_stack_top(stack_offset)?)?;
+        let operand = self.get_stack_top(stack_offset)?;
+        let result = match (operand, left_shift) {
+            (BigInt(value), true) => BigInt(math::big_shift_left(value, amount)?),
+            (BigInt(value), false) => BigInt(math::big_shift_right(value, amount)?),
+            (UBigInt(value), true) => UBigInt(math::big_shift_left(value, amount)?),
+            (UBigInt(value), false) => UBigInt(math::big_shift_right(value, amount)?),
+            (operand, true) => unary_integer_operation!(operand, |v| math::shift_left(v, amount)?, "shift"),
+            (operand, false) => unary_integer_operation!(operand, |v| math::shift_right(v, amount)?, "shift")
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn negate_operand(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let operand = self.get_stack_top(stack_offset)?;
+        let policy = self.overflow_policy;
+        let result = match operand {
+            Byte(num) => Byte(math::negate(num, policy)?),
+            Int16(num) => Int16(math::negate(num, policy)?),
+            Int32(num) => Int32(math::negate(num, policy)?),
+            Int64(num) => Int64(math::negate(num, policy)?),
+            Int128(num) => Int128(math::negate(num, policy)?),
+            Float32(num) => Float32(-num),
+            Float64(num) => Float64(-num),
+            Complex(num) => Complex(-num),
+            BigInt(num) => BigInt(-num),
+            Decimal16(num) => Decimal16(num.negate(policy)?),
+            Decimal32(num) => Decimal32(num.negate(policy)?),
+            Decimal64(num) => Decimal64(num.negate(policy)?),
+            Decimal128(num) => Decimal128(num.negate(policy)?),
+            _ => return Err(unsupported_operand("negate", &operand))
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn logic_negate_operand(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let operand = self.get_stack_top(stack_offset)?;
+        match operand {
+            Bool(value) => self.stack.push(Bool(!value)),
+            _ => return Err(unsupported_operand("negate", &operand))
         }
+        Ok(())
     }
 
-    fn equate_operands(&mut self, negate: bool, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
-        match (left, right) {
-            (Int16(left_num), Int16(right_num)) => self.stack.push(Bool((left_num == right_num) && !negate)),
-            (UInt16(left_num), UInt16(right_num)) => self.stack.push(Bool((left_num == right_num) && !negate)),
-            (Bool(left_val), Bool(right_val)) => self.stack.push(Bool((left_val == right_val) && !negate)),
-            _ => self.stack.push(Bool(false))
+    // Compares the top two operands and pushes whether the left one is greater
+    // than the right when `greater` is set, or less otherwise, or equal to it
+    // when `or_equal` is set. Numbers of different types are compared by their
+    // exact values, as `math::compare` describes. Comparisons involving NaN are
+    // always false.
+    fn compare_operand_size(&mut self, greater: bool, or_equal: bool, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let ordering = match (&left, &right) {
+            (Char(left), Char(right)) => left.partial_cmp(right),
+            _ => math::compare(&left, &right)?
+        };
+        let result = match ordering {
+            Some(Ordering::Less) => !greater,
+            Some(Ordering::Greater) => greater,
+            Some(Ordering::Equal) => or_equal,
+            None => false
+        };
+        self.stack.push(Bool(result));
+        Ok(())
+    }
+
+    // Converts the operand on top of the stack to the numeric or char type at
+    // `type_index`, failing if its value would be lost.
+    fn convert_operand(&mut self, type_index: u16, stack_offset: usize) -> Result<(), ErrorKind> {
+        let operand = self.get_stack_top(stack_offset)?;
+        let _type = self.type_registry.get(type_index)?;
+        let target = match math::NumericKind::from_name(&_type.get_canonical_name()) {
+            Some(target) => target,
+            None => return Err(ErrorKind::UnsupportedOperand { operation: "convert to", operand: _type.get_canonical_name() })
+        };
+        self.stack.push(math::convert(&operand, target, false)?);
+        Ok(())
+    }
+
+    // Numbers are equal when their values are, once promoted as for arithmetic,
+    // with floats following IEEE 754 so that NaN equals nothing, not even itself.
+    // Strings and chars compare by content. Arrays, functions and classes
+    // compare by identity, as comparing arrays element by element would never
+    // finish on one that contains itself. Instances compare by identity too,
+    // unless their class has an `equals` method taking the other operand. The
+    // left operand's method is tried first and then the right's, so `a == b`
+    // and `b == a` agree unless both classes define one that accepts the other.
+    fn equate_operands(&mut self, negate: bool, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
+        let name = self.string_pool.pool_str("equals");
+        let hook = match equals_method(&left, &right, &name) {
+            Some(method) => Some((method, vec![left.clone(), right.clone()])),
+            None => equals_method(&right, &left, &name).map(|method| (method, vec![right.clone(), left.clone()]))
+        };
+        if let Some((method, args)) = hook {
+            // An `equals` method runs to completion in the middle of an
+            // instruction, so it cannot yield even when a coroutine calls it.
+            // The coroutines stay in `running`, where the heap finds them.
+            let floor = mem::replace(&mut self.yield_floor, self.running.len());
+            let result = self.call_function(method, args);
+            self.yield_floor = floor;
+            result?;
+            return match self.get_stack_top(stack_offset)? {
+                Bool(equal) => { self.stack.push(Bool(equal != negate)); Ok(()) },
+                other => Err(ErrorKind::TypeMismatch { expected: Rc::new("silicon.lang.Boolean".to_string()), found: other.get_canonical_name() })
+            }
         }
+        self.stack.push(Bool(values_equal(left, right) != negate));
+        Ok(())
     }
 
-    fn type_test(&mut self, type_index: u16, stack_offset: usize) {
-        let operand = self.get_stack_top(stack_offset);
-        let _type = self.type_registry.get(type_index);
-        self.stack.push(Bool(_type.is(&operand)))
+    fn type_test(&mut self, type_index: u16, stack_offset: usize) -> Result<(), ErrorKind> {
+        let operand = self.get_stack_top(stack_offset)?;
+        let _type = self.type_registry.get(type_index)?;
+        self.stack.push(Bool(_type.is(&operand)));
+        Ok(())
     }
 
-    fn try_jump(&mut self, jump_index: u16, chunk: Rc<Chunk>, stack_offset: usize) -> bool {
-        let should_jump = !self.test_logic(stack_offset);
+    // Pops a boolean and jumps if it equals `jump_if`, returning whether it did.
+    fn try_jump(&mut self, jump_if: bool, jump_index: u16, chunk: Rc<Chunk>, stack_offset: usize) -> Result<bool, ErrorKind> {
+        let should_jump = self.test_logic(stack_offset)? == jump_if;
         if should_jump {
-            self.jump(jump_index, chunk);
-            return true
+            self.jump(jump_index, chunk)?;
+            return Ok(true)
         }
-        return false
+        Ok(false)
+    }
+
+    // Pops the value to switch on and jumps to the label the table has for it.
+    fn switch(&mut self, index: u16, chunk: Rc<Chunk>, stack_offset: usize) -> Result<(), ErrorKind> {
+        let table = chunk.switch_table.get(&index).ok_or(ErrorKind::UndefinedSwitch(index))?;
+        let value = self.get_stack_top(stack_offset)?;
+        let label = match SwitchKey::of(&value) {
+            Some(key) => table.target(&key),
+            // Integers too large for an i128 cannot have a case.
+            None if matches!(value, UInt128(_) | BigInt(_) | UBigInt(_)) => table.default,
+            None => return Err(unsupported_operand("switch on", &value))
+        };
+        self.jump(label, Rc::clone(&chunk))
     }
 
-    fn jump(&mut self, jump_index: u16, chunk: Rc<Chunk>) {
+    fn jump(&mut self, jump_index: u16, chunk: Rc<Chunk>) -> Result<(), ErrorKind> {
         match chunk.jump_table.get(&jump_index) {
             Some(jump_point) => {self.pc = *jump_point; },
-            None => panic!("Jump point does not exist")
+            None => return Err(ErrorKind::UndefinedJump(jump_index))
         }
+        Ok(())
     }
 
-    fn test_logic(&mut self, stack_offset: usize) -> bool {
-        let cond = self.get_stack_top(stack_offset);
-        return match cond {
-            Bool(value) => value.clone(),
-            _ => panic!()
-        };
-        panic!()
+    fn test_logic(&mut self, stack_offset: usize) -> Result<bool, ErrorKind> {
+        let cond = self.get_stack_top(stack_offset)?;
+        match cond {
+            Bool(value) => Ok(value),
+            _ => Err(ErrorKind::TypeMismatch { expected: Rc::new("silicon.lang.Boolean".to_string()), found: cond.get_canonical_name() })
+        }
     }
 
-    pub fn make_array(&mut self, array_size: u16, stack_offset: usize) {
-        let array : Vec<Instance> = self.split_stack(array_size as usize, stack_offset);
-        let mut _type = self.type_registry.get(0);
-        let mut check = false;
+    pub fn make_array(&mut self, array_size: u16, element_type_index: u16, stack_offset: usize) -> Result<(), ErrorKind> {
+        let element_type = self.type_registry.get(element_type_index)?;
+        let array : Vec<Instance> = self.split_stack(array_size as usize, stack_offset)?;
         for instance in &array {
-            if !check {
-                _type = self.type_registry.get_by_name(instance.get_canonical_name());
-                check = true
-            }
-            else {
-                if !_type.is(instance) {
-                    _type = self.type_registry.get(0);
-                }
+            if !element_type.is(instance) {
+                return Err(ErrorKind::TypeMismatch { expected: element_type.get_canonical_name(), found: instance.get_canonical_name() })
             }
         }
 
-        self.stack.push(Array(Rc::new(RefCell::new(array)), Rc::clone(&_type)));
+        let array_type = self.type_registry.get_array_type(element_type)?;
+        let array = self.heap.alloc_array(array, array_type);
+        self.stack.push(array);
+        Ok(())
     }
 
-    pub fn index_get(&mut self, stack_offset: usize) {
-        let index = self.get_stack_top(stack_offset);
-        let indexable = self.get_stack_top(stack_offset);
+    pub fn index_get(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let index = self.get_stack_top(stack_offset)?;
+        let indexable = self.get_stack_top(stack_offset)?;
 
         match indexable {
             Array(vec, _) => {
-                let mut index_num = 0;
-                match index {
-                    Byte(num) => index_num = num as usize,
-                    UByte(num) => index_num = num as usize,
-                    Int16(num) => index_num = num as usize,
-                    UInt16(num) => index_num = num as usize,
-                    _ => panic!("Invalid array index.")
-                };
-                match vec.borrow().get(index_num) {
-                    Some(instance) => self.stack.push(instance.to_owned()),
-                    None => panic!("No instance found at the given index.")
-                }
+                let vec = vec.borrow();
+                let index_num = to_index(&index, vec.len())?;
+                self.stack.push(vec[index_num].to_owned())
             },
             Str(string) => {
-                let mut index_num = 0;
-                match index {
-                    Byte(num) => index_num = num as usize,
-                    UByte(num) => index_num = num as usize,
-                    Int16(num) => index_num = num as usize,
-                    UInt16(num) => index_num = num as usize,
-                    _ => panic!("Invalid string index.")
-                };
-                match string.chars().nth(index_num) {
+                let length = self.string_pool.char_count(&string);
+                let index_num = to_index(&index, length)?;
+                let offset = self.string_pool.byte_offset(&string, index_num)
+                    .ok_or(ErrorKind::IndexOutOfBounds { index: index_num as i128, length })?;
+                match string[offset..].chars().next() {
                     Some(c) => self.stack.push(Char(c)),
-                    None => panic!("Invalid string index.")
+                    None => return Err(ErrorKind::IndexOutOfBounds { index: index_num as i128, length })
                 }
             }
-            _ => panic!("The instance is not indexable!")
+            _ => return Err(ErrorKind::NotIndexable(indexable.get_canonical_name()))
         }
+        Ok(())
     }
 
-    pub fn index_set(&mut self, stack_offset: usize) {
-        let item = self.get_stack_top(stack_offset);
-        let index = self.get_stack_top(stack_offset);
-        let array = self.get_stack_top(stack_offset);
+    pub fn index_set(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let item = self.get_stack_top(stack_offset)?;
+        let index = self.get_stack_top(stack_offset)?;
+        let array = self.get_stack_top(stack_offset)?;
 
         match array {
             Array(vec, _type) => {
-                let mut index_num = 0;
-                match index {
-                    Byte(num) => index_num = num as usize,
-                    UByte(num) => index_num = num as usize,
-                    Int16(num) => index_num = num as usize,
-                    UInt16(num) => index_num = num as usize,
-                    _ => panic!("Invalid array index.")
+                let mut vec = vec.borrow_mut();
+                let index_num = to_index(&index, vec.len())?;
+                let element_type = match _type.get_type_args().first() {
+                    Some(element_type) => Rc::clone(element_type),
+                    None => self.type_registry.get(0)?
                 };
-                vec.borrow_mut().remove(index_num);
-                if _type.is(&item) { vec.borrow_mut().insert(index_num, item) }
-                else { panic!("Type mismatch!") }
+                if !element_type.is(&item) {
+                    return Err(ErrorKind::TypeMismatch { expected: element_type.get_canonical_name(), found: item.get_canonical_name() })
+                }
+                vec[index_num] = item
             },
-            _ => panic!("The instance is not indexable!")
+            _ => return Err(ErrorKind::NotIndexable(array.get_canonical_name()))
         }
+        Ok(())
     }
 
-    pub fn concat(&mut self, stack_offset: usize) {
-        let right = self.get_stack_top(stack_offset);
-        let left = self.get_stack_top(stack_offset);
+    pub fn concat(&mut self, stack_offset: usize) -> Result<(), ErrorKind> {
+        let right = self.get_stack_top(stack_offset)?;
+        let left = self.get_stack_top(stack_offset)?;
 
         match left {
             Str(string) => {
@@ -386,45 +1063,156 @@ impl VM {
                 let pooled_string = self.string_pool.pool_string(new_string);
                 self.stack.push(Str(pooled_string))
             }
-            _ => panic!("Cannot concat operands!")
+            _ => return Err(unsupported_operands("concat", &left, &right))
         }
+        Ok(())
+    }
+
+    // Formats the top `count` values with the template in the string constant
+    // at `index`, interning only the result.
+    fn format_operands(&mut self, index: u16, count: u8, chunk: Rc<Chunk>, stack_offset: usize) -> Result<(), ErrorKind> {
+        let template = match chunk.get_const(index)? {
+            Str(template) => template,
+            constant => return Err(ErrorKind::TypeMismatch { expected: Rc::new("silicon.lang.String".to_string()), found: constant.get_canonical_name() })
+        };
+        let args = self.split_stack(count as usize, stack_offset)?;
+        let formatted = format::format(&template, &args)?;
+        self.stack.push(Str(self.string_pool.pool_string(formatted)));
+        Ok(())
     }
 
     pub fn enter_scope(&mut self, additional_size: u16, frame: Rc<RefCell<CallFrame>>) {
-        frame.borrow_mut().scope_allocations.push(additional_size);
+        frame.borrow_mut().scope_allocations.push(ScopeAllocation { size: additional_size, register_size: self.register.size });
         frame.borrow_mut().register_declare_offset += additional_size
     }
 
-    pub fn exit_scope(&mut self, frame: Rc<RefCell<CallFrame>>) {
+    // Drops the variables declared since the scope was entered, however many
+    // that turned out to be, such as when a `break` left before some were declared.
+    pub fn exit_scope(&mut self, frame: Rc<RefCell<CallFrame>>) -> Result<(), ErrorKind> {
         let allocation = frame.borrow_mut().scope_allocations.pop();
-        if let Some(amount) = allocation {
-            self.register.truncate(self.register.size - amount);
-            frame.borrow_mut().register_declare_offset -= amount;
-            return;
+        match allocation {
+            Some(allocation) => {
+                self.register.truncate(allocation.register_size);
+                frame.borrow_mut().register_declare_offset -= allocation.size;
+                Ok(())
+            },
+            None => Err(ErrorKind::ScopeUnderflow)
         }
-        println!("Exited inner scope that did not exist.")
     }
 
-    pub fn get_stack_top(&mut self, stack_offset: usize) -> Instance {
-        if self.stack.len() - stack_offset <= 0 {
-            panic!("The stack was empty!")
+    pub fn get_stack_top(&mut self, stack_offset: usize) -> Result<Instance, ErrorKind> {
+        if self.stack.len() <= stack_offset {
+            return Err(ErrorKind::StackUnderflow)
         }
 
-        return match self.stack.pop() {
-            Some(instance) => instance,
-            None => panic!("The stack was empty!")
+        match self.stack.pop() {
+            Some(instance) => Ok(instance),
+            None => Err(ErrorKind::StackUnderflow)
         }
     }
 
-    pub fn split_stack(&mut self, amount: usize, stack_offset: usize) -> Vec<Instance> {
-        let mut vec : Vec<Instance> = vec![];
-        for i in 0..amount {
-            let next = self.get_stack_top(stack_offset);
-            vec.push(next)
+    pub fn split_stack(&mut self, amount: usize, stack_offset: usize) -> Result<Vec<Instance>, ErrorKind> {
+        if self.stack.len() < stack_offset + amount {
+            return Err(ErrorKind::StackUnderflow)
         }
-        vec.reverse();
-        return vec
+        Ok(self.stack.split_off(self.stack.len() - amount))
+    }
+}
+
+fn unsupported_operands(operation: &'static str, left: &Instance, right: &Instance) -> ErrorKind {
+    ErrorKind::UnsupportedOperands { operation, left: left.get_canonical_name(), right: right.get_canonical_name() }
+}
+
+fn unsupported_operand(operation: &'static str, operand: &Instance) -> ErrorKind {
+    ErrorKind::UnsupportedOperand { operation, operand: operand.get_canonical_name() }
+}
+
+// The `equals` method of `this`'s class, if it has one that takes `other`.
+fn equals_method(this: &Instance, other: &Instance, name: &Rc<String>) -> Option<Rc<Function>> {
+    match this {
+        ClassInstance(instance) => instance.class.get_method(name)
+            .filter(|method| method.param_types.len() == 2 && method.param_types[1].is(other)),
+        _ => None
+    }
+}
+
+// Equality for everything but instances with an `equals` method; see `VM::equate_operands`.
+fn values_equal(left: Instance, right: Instance) -> bool {
+    let (left, right) = match math::promote(left, right) {
+        Ok(promoted) => promoted,
+        Err(_) => return false
+    };
+    match (&left, &right) {
+        (Bool(left), Bool(right)) => left == right,
+        (Byte(left), Byte(right)) => left == right,
+        (UByte(left), UByte(right)) => left == right,
+        (Int16(left), Int16(right)) => left == right,
+        (UInt16(left), UInt16(right)) => left == right,
+        (Int32(left), Int32(right)) => left == right,
+        (UInt32(left), UInt32(right)) => left == right,
+        (Int64(left), Int64(right)) => left == right,
+        (UInt64(left), UInt64(right)) => left == right,
+        (Int128(left), Int128(right)) => left == right,
+        (UInt128(left), UInt128(right)) => left == right,
+        (Float32(left), Float32(right)) => left == right,
+        (Float64(left), Float64(right)) => left == right,
+        (BigInt(left), BigInt(right)) => left == right,
+        (UBigInt(left), UBigInt(right)) => left == right,
+        (Decimal16(left), Decimal16(right)) => left.compare(*right) == Ordering::Equal,
+        (UDecimal16(left), UDecimal16(right)) => left.compare(*right) == Ordering::Equal,
+        (Decimal32(left), Decimal32(right)) => left.compare(*right) == Ordering::Equal,
+        (UDecimal32(left), UDecimal32(right)) => left.compare(*right) == Ordering::Equal,
+        (Decimal64(left), Decimal64(right)) => left.compare(*right) == Ordering::Equal,
+        (UDecimal64(left), UDecimal64(right)) => left.compare(*right) == Ordering::Equal,
+        (Decimal128(left), Decimal128(right)) => left.compare(*right) == Ordering::Equal,
+        (UDecimal128(left), UDecimal128(right)) => left.compare(*right) == Ordering::Equal,
+        (Complex(left), Complex(right)) => left == right,
+        (Char(left), Char(right)) => left == right,
+        // Pooled strings with the same content are usually the same string.
+        (Str(left), Str(right)) => Rc::ptr_eq(left, right) || left == right,
+        (Array(left, _), Array(right, _)) => Rc::ptr_eq(left, right),
+        (ClassInstance(left), ClassInstance(right)) => Rc::ptr_eq(left, right),
+        (Class(left), Class(right)) => Rc::ptr_eq(left, right),
+        (Func(left), Func(right)) => Rc::ptr_eq(left, right),
+        (Instance::Coroutine(left), Instance::Coroutine(right)) => Rc::ptr_eq(left, right),
+        (Void, Void) => true,
+        _ => false
+    }
+}
+
+fn is_decimal(instance: &Instance) -> bool {
+    matches!(instance, Decimal16(_) | UDecimal16(_) | Decimal32(_) | UDecimal32(_) | Decimal64(_) | UDecimal64(_) | Decimal128(_) | UDecimal128(_))
+}
+
+fn exponent<T>(value: T) -> Result<u32, ErrorKind> where T: TryInto<u32> + Zero + PartialOrd + Clone + Display {
+    if value < T::zero() {
+        return Err(ErrorKind::NegativeExponent)
+    }
+    match value.clone().try_into() {
+        Ok(exponent) => Ok(exponent),
+        Err(_) => Err(ErrorKind::LossyConversion { value: Rc::new(value.to_string()), target: Rc::new("silicon.lang.UInt32".to_string()) })
+    }
+}
+
+// Converts an integer instance to an index into a sequence of `length` items.
+fn to_index(index: &Instance, length: usize) -> Result<usize, ErrorKind> {
+    let index_num: i128 = match index {
+        Byte(num) => *num as i128,
+        UByte(num) => *num as i128,
+        Int16(num) => *num as i128,
+        UInt16(num) => *num as i128,
+        Int32(num) => *num as i128,
+        UInt32(num) => *num as i128,
+        Int64(num) => *num as i128,
+        UInt64(num) => *num as i128,
+        Int128(num) => *num,
+        UInt128(num) => i128::try_from(*num).unwrap_or(i128::MAX),
+        _ => return Err(ErrorKind::InvalidIndex(index.get_canonical_name()))
+    };
+    if index_num < 0 || index_num >= length as i128 {
+        return Err(ErrorKind::IndexOutOfBounds { index: index_num, length })
     }
+    Ok(index_num as usize)
 }
 
 /*
@@ -435,7 +1223,26 @@ pub struct CallFrame {
     register_access_offset: u16,
     register_declare_offset: u16,
     stack_offset: usize,
-    scope_allocations: Vec<u16>
+    scope_allocations: Vec<ScopeAllocation>,
+    // The handlers installed by `Try`, innermost last.
+    handlers: Vec<ActiveHandler>,
+    // The variables captured by the closure running in this frame.
+    pub(crate) upvalues: Vec<Rc<RefCell<Variable>>>
+}
+
+// A handler installed by `Try`, along with what the frame looked like then.
+struct ActiveHandler {
+    label: u16,
+    catch_type: Option<Rc<Type>>,
+    scope_depth: usize,
+    register_size: u16,
+    stack_size: usize
+}
+
+// A scope entered by `EnterScope`, along with the size of the register when it was.
+struct ScopeAllocation {
+    size: u16,
+    register_size: u16
 }
 
 impl CallFrame {
@@ -444,7 +1251,9 @@ impl CallFrame {
             register_access_offset: 0,
             register_declare_offset: 0,
             stack_offset: 0,
-            scope_allocations: vec![]
+            scope_allocations: vec![],
+            handlers: vec![],
+            upvalues: vec![]
         }
     }
 
@@ -453,14 +1262,34 @@ impl CallFrame {
             register_access_offset,
             register_declare_offset,
             stack_offset,
-            scope_allocations: vec![]
+            scope_allocations: vec![],
+            handlers: vec![],
+            upvalues: vec![]
+        }
+    }
+
+    // Moves the frame of a coroutine being resumed from where its variables
+    // and operands were in the register and on the stack to where they are now.
+    pub(crate) fn rebase(&mut self, from_register: u16, to_register: u16, from_stack: usize, to_stack: usize) {
+        let register = |offset: u16| offset - from_register + to_register;
+        let stack = |offset: usize| offset - from_stack + to_stack;
+        self.register_access_offset = register(self.register_access_offset);
+        self.register_declare_offset = register(self.register_declare_offset);
+        self.stack_offset = stack(self.stack_offset);
+        for allocation in &mut self.scope_allocations {
+            allocation.register_size = register(allocation.register_size);
+        }
+        for handler in &mut self.handlers {
+            handler.register_size = register(handler.register_size);
+            handler.stack_size = stack(handler.stack_size);
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Register {
-    internal: HashMap<u16, RefCell<Variable>>,
+    // Variables are shared with the closures that capture them.
+    internal: HashMap<u16, Rc<RefCell<Variable>>>,
     size: u16,
     flag: bool
 }
@@ -474,51 +1303,65 @@ impl Register {
         }
     }
 
-    pub fn declare(&mut self, is_const: bool, instance: Instance, _type: Rc<Type>) {
+    pub fn declare(&mut self, is_const: bool, instance: Instance, _type: Rc<Type>) -> Result<(), ErrorKind> {
+        if !_type.is(&instance) {
+            return Err(ErrorKind::TypeMismatch { expected: _type.get_canonical_name(), found: instance.get_canonical_name() })
+        }
+
         let index = self.size;
-        self.size += 1;
+        self.size = self.size.checked_add(1).ok_or(ErrorKind::RegisterOverflow)?;
 
         let variable = Variable::new(is_const, instance, _type);
-        self.internal.insert(index, RefCell::from(variable));
+        self.internal.insert(index, Rc::new(RefCell::new(variable)));
+        Ok(())
     }
 
-    pub fn set(&mut self, index: u16, instance: Instance) {
+    pub fn set(&mut self, index: u16, instance: Instance) -> Result<(), ErrorKind> {
         match self.internal.get(&index) {
-            None => panic!(),
-            Some(var) => {
-                var.borrow_mut().set(instance)
-            },
+            None => Err(ErrorKind::UndefinedVariable(index)),
+            Some(var) => var.borrow_mut().set(instance),
         }
     }
 
-    pub fn get(&self, index: u16) -> Instance {
+    pub fn get(&self, index: u16) -> Result<Instance, ErrorKind> {
         match self.internal.get(&index) {
-            None => panic!(),
-            Some(var) => var.borrow().stored.to_owned(),
+            None => Err(ErrorKind::UndefinedVariable(index)),
+            Some(var) => Ok(var.borrow().stored.to_owned()),
         }
     }
 
-    pub fn truncate(&mut self, to_size: u16) {
-        if to_size == self.size || self.size == 0 {
-            return;
+    // The variable in `index`, to be shared with a closure.
+    pub fn capture(&self, index: u16) -> Option<Rc<RefCell<Variable>>> {
+        self.internal.get(&index).map(Rc::clone)
+    }
+
+    // Removes the variables from `at` onwards and returns them in order.
+    pub fn split_off(&mut self, at: u16) -> Vec<Rc<RefCell<Variable>>> {
+        let variables = (at..self.size).filter_map(|index| self.internal.remove(&index)).collect();
+        self.size = self.size.min(at);
+        variables
+    }
+
+    // Adds variables taken by `split_off` back on top of the register.
+    pub fn extend(&mut self, variables: Vec<Rc<RefCell<Variable>>>) -> Result<(), ErrorKind> {
+        let count = u16::try_from(variables.len()).map_err(|_| ErrorKind::RegisterOverflow)?;
+        let base = self.size;
+        self.size = self.size.checked_add(count).ok_or(ErrorKind::RegisterOverflow)?;
+        for (index, variable) in (base..).zip(variables) {
+            self.internal.insert(index, variable);
         }
+        Ok(())
+    }
 
-        let mut to_clear = self.size - 1;
-        loop {
-            self.internal.remove(&to_clear);
+    pub fn truncate(&mut self, to_size: u16) {
+        while self.size > to_size {
             self.size -= 1;
-            if self.size == 0 {
-                return;
-            }
-            to_clear = to_clear - 1;
-            if to_size == self.size {
-                return;
-            }
+            self.internal.remove(&self.size);
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypeRegistry {
     type_map: HashMap<u16, Rc<Type>>,
     name_map: HashMap<Rc<String>, u16>,
@@ -544,44 +1387,229 @@ impl TypeRegistry {
         _self.register(Type::new(string_pool.pool_str("silicon.lang.UInt64")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.Int128")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.UInt128")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Decimal16")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.UDecimal16")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Decimal32")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.UDecimal32")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Decimal64")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.UDecimal64")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Decimal128")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.UDecimal128")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Complex")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.Float32")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.Float64")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.BigInt")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.UBigInt")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.Char")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.String")));
-        _self.register(Type::new(string_pool.pool_str("silicon.lang.Array")));
+        _self.register(Type::new_generic(string_pool.pool_str("silicon.lang.Array"), 1));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.Func")));
         _self.register(Type::new(string_pool.pool_str("silicon.lang.Void")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Class")));
+        _self.register(Type::new(string_pool.pool_str("silicon.lang.Coroutine")));
         _self
     }
 
     fn register(&mut self, _type: Type) {
-        let index = self.size;
-        let name = Rc::clone(&_type.canonical_name);
+        self.register_type(Rc::new(_type)).expect("built-in types are registered once each");
+    }
 
-        self.size += 1;
+    // Registers a type defined by a program, such as a class, and returns its
+    // index. Built-in types cannot be redefined.
+    pub(crate) fn register_type(&mut self, _type: Rc<Type>) -> Result<u16, ErrorKind> {
+        let name = _type.get_canonical_name();
+        if self.name_map.contains_key(&name) {
+            return Err(ErrorKind::DuplicateType(name))
+        }
 
+        // Reifying generic types registers types as a program runs, so this
+        // can run out of indices.
+        let index = self.size;
+        self.size = self.size.checked_add(1).ok_or(ErrorKind::TypeRegistryOverflow)?;
         self.name_map.insert(name, index);
-        self.type_map.insert(index, Rc::from(_type));
+        self.type_map.insert(index, _type);
+        Ok(index)
+    }
+
+    // Creates a type defined by a program and registers it. Each parent comes
+    // with the type arguments the new type passes to it, which are empty for
+    // parents that are not generic. Parents whose arguments do not depend on
+    // the new type's parameters are reified straight away.
+    pub(crate) fn define_type(&mut self, canonical_name: Rc<String>, type_param_count: usize, supertype: Option<(Rc<Type>, Vec<TypeArg>)>, interfaces: Vec<(Rc<Type>, Vec<TypeArg>)>) -> Result<Rc<Type>, ErrorKind> {
+        let supertype = match supertype {
+            Some((parent, args)) => Some(self.define_parent(parent, args, type_param_count)?),
+            None => None
+        };
+        let interfaces = interfaces.into_iter()
+            .map(|(parent, args)| self.define_parent(parent, args, type_param_count))
+            .collect::<Result<Vec<(Rc<Type>, Vec<TypeArg>)>, ErrorKind>>()?;
+        let _type = Rc::new(Type::new_with_parent_args(canonical_name, type_param_count, supertype, interfaces));
+        self.register_type(Rc::clone(&_type))?;
+        Ok(_type)
+    }
+
+    fn define_parent(&mut self, parent: Rc<Type>, args: Vec<TypeArg>, type_param_count: usize) -> Result<(Rc<Type>, Vec<TypeArg>), ErrorKind> {
+        if args.is_empty() && !parent.is_generic() {
+            return Ok((parent, args))
+        }
+        parent.check_type_args(args.len())?;
+        for arg in &args {
+            arg.check(type_param_count)?;
+        }
+        if args.iter().any(TypeArg::uses_params) {
+            return Ok((parent, args))
+        }
+        let type_args = args.iter().map(|arg| self.resolve_type_arg(arg, &[])).collect::<Result<Vec<Rc<Type>>, ErrorKind>>()?;
+        let index = self.reify(&parent, type_args)?;
+        Ok((self.get(index)?, vec![]))
+    }
+
+    // Reifies `generic` with `type_args`, reusing the type registered by an
+    // earlier reification with the same arguments. Its parents are reified
+    // with the arguments it passes them, and registered the same way.
+    pub(crate) fn reify(&mut self, generic: &Type, type_args: Vec<Rc<Type>>) -> Result<u16, ErrorKind> {
+        generic.check_type_args(type_args.len())?;
+        if let Some(index) = self.get_index(&parameterised_name(&generic.canonical_name, &type_args)) {
+            return Ok(index)
+        }
+        let reified = generic.reify(type_args, |parent, args, type_args| {
+            let parent_args = args.iter().map(|arg| self.resolve_type_arg(arg, type_args)).collect::<Result<Vec<Rc<Type>>, ErrorKind>>()?;
+            let index = self.reify(parent, parent_args)?;
+            self.get(index)
+        })?;
+        self.register_type(Rc::new(reified))
+    }
+
+    // The type `arg` stands for in a type reified with `type_args`.
+    fn resolve_type_arg(&mut self, arg: &TypeArg, type_args: &[Rc<Type>]) -> Result<Rc<Type>, ErrorKind> {
+        match arg {
+            TypeArg::Param(index) => type_args.get(*index).cloned().ok_or(ErrorKind::UndefinedTypeParam(*index)),
+            TypeArg::Concrete(_type) => Ok(Rc::clone(_type)),
+            TypeArg::Applied(generic, args) => {
+                let args = args.iter().map(|arg| self.resolve_type_arg(arg, type_args)).collect::<Result<Vec<Rc<Type>>, ErrorKind>>()?;
+                let index = self.reify(generic, args)?;
+                self.get(index)
+            }
+        }
+    }
+
+    pub(crate) fn get_array_type(&mut self, element_type: Rc<Type>) -> Result<Rc<Type>, ErrorKind> {
+        let array_type = self.get_by_name(Rc::new("silicon.lang.Array".to_string()))?;
+        let index = self.reify(&array_type, vec![element_type])?;
+        self.get(index)
+    }
+
+    // Looks up a type by name, reifying names such as
+    // `silicon.lang.Array<silicon.lang.Int32>` the first time they are seen.
+    // Names may come from files, so their type arguments may only be nested
+    // `MAX_NESTING` levels deep.
+    pub(crate) fn resolve_name(&mut self, name: &str) -> Result<u16, ErrorKind> {
+        if type_nesting(name) > MAX_NESTING {
+            return Err(ErrorKind::TypeTooDeeplyNested)
+        }
+        self.resolve_nested(name)
+    }
+
+    fn resolve_nested(&mut self, name: &str) -> Result<u16, ErrorKind> {
+        if let Some(index) = self.get_index(&Rc::new(name.to_string())) {
+            return Ok(index)
+        }
+
+        let unknown = || ErrorKind::UnknownType(Rc::new(name.to_string()));
+        let (base, type_args) = match (name.find('<'), name.strip_suffix('>')) {
+            (Some(open), Some(rest)) => (&name[..open], &rest[open + 1..]),
+            _ => return Err(unknown())
+        };
+        let generic = match self.get_index(&Rc::new(base.to_string())) {
+            Some(index) => self.get(index)?,
+            None => return Err(unknown())
+        };
+        let mut args = vec![];
+        for type_arg in split_type_list(type_args) {
+            let index = self.resolve_nested(type_arg)?;
+            args.push(self.get(index)?);
+        }
+        self.reify(&generic, args)
     }
 
-    fn get(&self, index: u16) -> Rc<Type> {
-        match self.type_map.get(&index) {
-            None => panic!("Non-existant type!"),
-            Some(t) => Rc::clone(t),
+    pub(crate) fn get(&self, index: u16) -> Result<Rc<Type>, ErrorKind> {
+        match self.try_get(index) {
+            None => Err(ErrorKind::UnknownTypeIndex(index)),
+            Some(t) => Ok(t),
         }
     }
 
-    fn get_by_name(&self, name: Rc<String>) -> Rc<Type> {
+    pub(crate) fn try_get(&self, index: u16) -> Option<Rc<Type>> {
+        self.type_map.get(&index).map(Rc::clone)
+    }
+
+    pub(crate) fn get_index(&self, name: &Rc<String>) -> Option<u16> {
+        self.name_map.get(name).copied()
+    }
+
+    pub(crate) fn get_by_name(&self, name: Rc<String>) -> Result<Rc<Type>, ErrorKind> {
         match self.name_map.get(&name) {
-            None => panic!("Type {} does not exist.", name),
+            None => Err(ErrorKind::UnknownType(name)),
             Some(i) => self.get(*i),
         }
     }
 }
 
+// How many levels of type arguments `name` nests.
+fn type_nesting(name: &str) -> usize {
+    let mut depth: usize = 0;
+    let mut deepest = 0;
+    for c in name.chars() {
+        match c {
+            '<' => { depth += 1; deepest = deepest.max(depth) },
+            '>' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    deepest
+}
+
+// Splits a comma separated list of types, ignoring commas nested inside `<>`.
+pub(crate) fn split_type_list(text: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(text[start..i].trim());
+                start = i + 1
+            },
+            _ => {}
+        }
+    }
+    if !text[start..].trim().is_empty() {
+        items.push(text[start..].trim())
+    }
+    items
+}
+
 pub enum InstructionResult{
     Continue,
     Return,
     ReturnWith(Instance),
-    ExitScope,
+    // The frame was suspended by a coroutine yielding; see `VM::resume`.
+    Yield,
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use super::*;
+
+    #[test]
+    fn exponents_must_fit_a_u32() {
+        assert!(matches!(exponent(3i64), Ok(3)));
+        assert!(matches!(exponent(-1i64), Err(ErrorKind::NegativeExponent)));
+        assert!(matches!(exponent(5_000_000_000i64), Err(ErrorKind::LossyConversion { .. })));
+        assert!(matches!(exponent(BigInt::from(-5_000_000_000i64)), Err(ErrorKind::NegativeExponent)));
+        assert!(matches!(exponent(BigInt::from(5_000_000_000i64)), Err(ErrorKind::LossyConversion { .. })));
+    }
 }
@@ -1,21 +1,85 @@
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::{Rc, Weak};
 
 pub struct StringPool {
-    pool: HashSet<Rc<String>>
+    pool: HashSet<Rc<String>>,
+    // The number of strings `pool` may grow to before the ones nothing else
+    // references are swept out of it.
+    pool_limit: usize,
+    lookups: u64,
+    hits: u64,
+    swept: u64,
+    // Where each char of a string starts, so that strings can be indexed by
+    // char without walking them. Keyed by the string's address and checked
+    // against a weak reference, since the address may be reused once the
+    // string is dropped.
+    char_indices: HashMap<usize, CharIndex>,
+    // The number of entries `char_indices` may grow to before dead ones are removed.
+    char_index_limit: usize
 }
 
+struct CharIndex {
+    string: Weak<String>,
+    // None for ASCII strings, where chars and bytes line up.
+    offsets: Option<Rc<[u32]>>
+}
+
+// A snapshot of how the pool is doing, for `--pool-stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolStats {
+    pub strings: usize,
+    pub bytes: usize,
+    pub lookups: u64,
+    pub hits: u64,
+    pub swept: u64
+}
+
+impl PoolStats {
+    // The share of lookups that found the string already pooled, or 0 before any lookup.
+    pub fn hit_rate(&self) -> f64 {
+        match self.lookups {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} strings ({} bytes), {} lookups, {} hits ({:.1}%), {} swept",
+            self.strings, self.bytes, self.lookups, self.hits, self.hit_rate() * 100.0, self.swept)
+    }
+}
+
+const MIN_POOL_LIMIT: usize = 256;
+const MIN_CHAR_INDEX_LIMIT: usize = 64;
+
 impl StringPool {
     pub(crate) fn new() -> StringPool {
         StringPool {
-            pool: Default::default()
+            pool: Default::default(),
+            pool_limit: MIN_POOL_LIMIT,
+            lookups: 0,
+            hits: 0,
+            swept: 0,
+            char_indices: Default::default(),
+            char_index_limit: MIN_CHAR_INDEX_LIMIT
         }
     }
 
     pub(crate) fn pool_string(&mut self, to_pool: String) -> Rc<String> {
-        return match self.pool.get(&to_pool) {
-            Some(rc) => Rc::clone(rc),
+        self.lookups += 1;
+        match self.pool.get(&to_pool) {
+            Some(rc) => {
+                self.hits += 1;
+                Rc::clone(rc)
+            },
             None => {
+                if self.pool.len() >= self.pool_limit {
+                    self.sweep();
+                    self.pool_limit = (self.pool.len() * 2).max(MIN_POOL_LIMIT);
+                }
                 let new_rc: Rc<String> = Rc::from(to_pool);
                 self.pool.insert(Rc::clone(&new_rc));
                 new_rc
@@ -26,4 +90,71 @@ impl StringPool {
     pub(crate) fn pool_str(&mut self, to_pool: &str) -> Rc<String> {
         self.pool_string(to_pool.to_string())
     }
-}
\ No newline at end of file
+
+    // Drops the strings only the pool still references, returning how many went.
+    pub(crate) fn sweep(&mut self) -> usize {
+        let before = self.pool.len();
+        self.pool.retain(|string| Rc::strong_count(string) > 1);
+        self.char_indices.retain(|_, index| index.string.strong_count() > 0);
+        let swept = before - self.pool.len();
+        self.swept += swept as u64;
+        swept
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        PoolStats {
+            strings: self.pool.len(),
+            bytes: self.pool.iter().map(|string| string.len()).sum(),
+            lookups: self.lookups,
+            hits: self.hits,
+            swept: self.swept
+        }
+    }
+
+    // The number of chars in `string`.
+    pub(crate) fn char_count(&mut self, string: &Rc<String>) -> usize {
+        match self.char_offsets(string) {
+            Some(offsets) => offsets.len(),
+            None => string.len()
+        }
+    }
+
+    // The byte offset of the char at `index`, or the length of `string` in
+    // bytes when `index` is its length in chars.
+    pub(crate) fn byte_offset(&mut self, string: &Rc<String>, index: usize) -> Option<usize> {
+        match self.char_offsets(string) {
+            Some(offsets) if index == offsets.len() => Some(string.len()),
+            Some(offsets) => offsets.get(index).map(|offset| *offset as usize),
+            None if index <= string.len() => Some(index),
+            None => None
+        }
+    }
+
+    // The index of the char starting at the byte `offset` of `string`.
+    pub(crate) fn char_index(&mut self, string: &Rc<String>, offset: usize) -> usize {
+        match self.char_offsets(string) {
+            Some(offsets) => offsets.binary_search(&(offset as u32)).unwrap_or_else(|index| index),
+            None => offset
+        }
+    }
+
+    fn char_offsets(&mut self, string: &Rc<String>) -> Option<Rc<[u32]>> {
+        let key = Rc::as_ptr(string) as usize;
+        if let Some(index) = self.char_indices.get(&key) {
+            if index.string.upgrade().is_some_and(|indexed| Rc::ptr_eq(&indexed, string)) {
+                return index.offsets.clone()
+            }
+        }
+
+        if self.char_indices.len() >= self.char_index_limit {
+            self.char_indices.retain(|_, index| index.string.strong_count() > 0);
+            self.char_index_limit = (self.char_indices.len() * 2).max(MIN_CHAR_INDEX_LIMIT);
+        }
+        let offsets: Option<Rc<[u32]>> = match string.is_ascii() {
+            true => None,
+            false => Some(string.char_indices().map(|(offset, _)| offset as u32).collect())
+        };
+        self.char_indices.insert(key, CharIndex { string: Rc::downgrade(string), offsets: offsets.clone() });
+        offsets
+    }
+}
@@ -0,0 +1,103 @@
+// Checks a loaded chunk before it runs.
+//
+// Every jump, handler and switch target has to be an entry in the chunk's
+// jump table, and every `Try` and `Switch` has to name an entry in its handler
+// or switch table, so that a malformed file is rejected when it is loaded
+// rather than failing part way through running. The bodies of functions and
+// methods in the constant table are checked the same way. Both
+// `bytecode::read_chunk` and `assembler::assemble` verify the chunks they
+// return, so every chunk a VM is given has been through here.
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use crate::error::ErrorKind;
+use crate::instance::{Instance, FunctionBody};
+use crate::opcode::{Chunk, OpCode, SwitchCases};
+
+#[derive(Debug)]
+pub enum VerifyError {
+    // The instruction at the given offset refers to something the chunk lacks.
+    Instruction(usize, ErrorKind),
+    // An entry in the handler or switch table jumps to a label the chunk lacks.
+    Handler(u16, ErrorKind),
+    Switch(u16, ErrorKind),
+    // The body of the function, or of a method of the class, in the given
+    // constant slot failed to verify.
+    InConstant(u16, Box<VerifyError>)
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Instruction(offset, kind) => write!(f, "{} (at instruction {:04})", kind, offset),
+            VerifyError::Handler(index, kind) => write!(f, "{} (in handler {})", kind, index),
+            VerifyError::Switch(index, kind) => write!(f, "{} (in switch {})", kind, index),
+            VerifyError::InConstant(slot, inner) => write!(f, "{}, in constant slot {}", inner, slot)
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+pub fn verify(chunk: &Chunk) -> Result<(), VerifyError> {
+    let check_label = |label: u16| match chunk.jump_table.contains_key(&label) {
+        true => Ok(()),
+        false => Err(ErrorKind::UndefinedJump(label))
+    };
+
+    // Sorted so that the first error is always the same one.
+    let mut handlers: Vec<&u16> = chunk.handler_table.keys().collect();
+    handlers.sort();
+    for index in handlers {
+        check_label(chunk.handler_table[index].label).map_err(|kind| VerifyError::Handler(*index, kind))?;
+    }
+
+    let mut switches: Vec<&u16> = chunk.switch_table.keys().collect();
+    switches.sort();
+    for index in switches {
+        let table = &chunk.switch_table[index];
+        let labels: Vec<u16> = match &table.cases {
+            SwitchCases::Dense { labels, .. } => labels.clone(),
+            SwitchCases::Sparse(cases) => cases.values().copied().collect()
+        };
+        for label in std::iter::once(table.default).chain(labels) {
+            check_label(label).map_err(|kind| VerifyError::Switch(*index, kind))?;
+        }
+    }
+
+    for (offset, op) in chunk.op_codes.iter().enumerate() {
+        let checked = match op {
+            OpCode::Jump(_, label) | OpCode::JumpIfTrue(label) => check_label(*label),
+            OpCode::Try(index) if !chunk.handler_table.contains_key(index) => Err(ErrorKind::UndefinedHandler(*index)),
+            OpCode::Switch(index) if !chunk.switch_table.contains_key(index) => Err(ErrorKind::UndefinedSwitch(*index)),
+            _ => Ok(())
+        };
+        checked.map_err(|kind| VerifyError::Instruction(offset, kind))?;
+    }
+
+    let mut slots: Vec<&u16> = chunk.const_table.keys().collect();
+    slots.sort();
+    for slot in slots {
+        let bodies: Vec<Rc<Chunk>> = match &chunk.const_table[slot] {
+            Instance::Func(function) => body_of(&function.body).into_iter().collect(),
+            Instance::Class(class) => {
+                let mut names: Vec<_> = class.methods.keys().collect();
+                names.sort();
+                names.into_iter().filter_map(|name| body_of(&class.methods[name].body)).collect()
+            },
+            _ => vec![]
+        };
+        for body in bodies {
+            verify(&body).map_err(|error| VerifyError::InConstant(*slot, Box::new(error)))?;
+        }
+    }
+    Ok(())
+}
+
+fn body_of(body: &FunctionBody) -> Option<Rc<Chunk>> {
+    match body {
+        FunctionBody::Bytecode(chunk) => Some(Rc::clone(chunk)),
+        FunctionBody::Native(_) => None
+    }
+}
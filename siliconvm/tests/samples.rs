@@ -0,0 +1,46 @@
+// Runs each program in `test/` and checks what it prints against the
+// `.out` file next to it, both from the source and once assembled to bytecode.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn samples() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+    let mut samples: Vec<PathBuf> = fs::read_dir(&dir).expect("the test directory exists")
+        .map(|entry| entry.expect("the test directory is readable").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "sasm"))
+        .collect();
+    samples.sort();
+    assert!(!samples.is_empty(), "no samples in {}", dir.display());
+    samples
+}
+
+fn silicon(args: &[&Path]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_silicon")).args(args).output().expect("silicon runs");
+    let stdout = String::from_utf8(output.stdout).expect("silicon prints UTF-8");
+    assert!(output.status.success(), "silicon {:?} failed:\n{}", args, stdout);
+    stdout
+}
+
+fn expected(sample: &Path) -> String {
+    let path = sample.with_extension("out");
+    fs::read_to_string(&path).unwrap_or_else(|error| panic!("could not read {}: {}", path.display(), error))
+}
+
+#[test]
+fn samples_print_what_they_expect() {
+    for sample in samples() {
+        assert_eq!(silicon(&[&sample]), expected(&sample), "{}", sample.display());
+    }
+}
+
+#[test]
+fn assembled_samples_print_the_same() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    for sample in samples() {
+        let assembled = dir.join(sample.with_extension("silc").file_name().unwrap());
+        silicon(&[Path::new("--assemble"), &sample, &assembled]);
+        assert_eq!(silicon(&[&assembled]), expected(&sample), "{}", sample.display());
+    }
+}